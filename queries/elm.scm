@@ -57,7 +57,20 @@
   (block_comment) @allow_blank_line_before
 )
 
+; The module doc comment is left to the presets
 (file
+  [
+    (import_clause)
+    (value_declaration)
+    (type_declaration)
+    (type_alias_declaration)
+    (type_annotation)
+    (port_annotation)
+    (infix_declaration)
+    (line_comment)
+    (block_comment)
+  ]
+  .
   (block_comment) @append_hardline
   .
   (block_comment) @allow_blank_line_before
@@ -158,34 +171,6 @@
   (#delimiter! "__DECL_DELIMITER__")
 )
 
-; Preserve blank lines after top-level line comments before declarations
-; Note: This rule allows preserving blank lines from input for regular comments.
-; Section header comments after imports are handled by the rule below.
-(file
-  (line_comment)
-  .
-  [
-    (value_declaration)
-    (type_declaration)
-    (type_alias_declaration)
-    (port_annotation)
-  ] @allow_blank_line_before
-)
-
-; Section header comments after imports get proper spacing.
-; The line_comment already has @append_hardline adding one newline,
-; so we add __SECTION_COMMENT_DELIMITER__ (newlines_between_decls newlines) to get
-; the total spacing matching the configured newlines_between_decls + 1 blank lines.
-; This rule is specifically for line_comment followed by type_annotation at file level.
-(file
-  (line_comment) @append_delimiter
-  .
-  (type_annotation)
-  (#delimiter! "__SECTION_COMMENT_DELIMITER__")
-)
-
-
-
 ; ==============================================================================
 ; Exposing lists
 ; ==============================================================================
@@ -819,10 +804,13 @@
 ; Function calls
 ; ==============================================================================
 
-; Spaced softline between consecutive children in function calls (target and args)
+; Spaced softline between consecutive arguments in function calls
 ; In single-line mode: space between args
 ; In multi-line mode: each arg on its own line
+; The line break before the first argument depends on the preset, see
+; queries/elmfmt.scm and queries/elm_format.scm
 (function_call_expr
+  (_)
   (_) @append_spaced_softline
   .
   (_)
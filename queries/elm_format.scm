@@ -0,0 +1,141 @@
+; elm-format preset
+; Used instead of queries/elmfmt.scm with `preset: elm-format`, to reproduce
+; the output of elm-format.
+;
+; Blank lines are added as a "\n" delimiter at the end of the preceding node,
+; so that the hardline after it produces the indentation of the next line.
+
+; Declaration bodies always go on their own line
+(value_declaration
+  (eq) @append_hardline
+)
+
+(type_alias_declaration
+  (eq) @append_hardline
+)
+
+; Custom type variants always go on their own lines
+(type_declaration
+  (eq) @prepend_hardline
+)
+
+(type_declaration
+  "|" @prepend_hardline
+)
+
+; Case branch bodies always go on their own line
+(case_of_branch
+  (arrow) @append_hardline
+)
+
+; Blank line between case branches
+(case_of_expr
+  (case_of_branch
+    (_) @append_delimiter
+    .
+  )
+  .
+  (case_of_branch)
+  (#delimiter! "\n")
+)
+
+; If expressions are always multi-line
+(if_else_expr
+  "then" @append_hardline
+)
+
+(if_else_expr
+  "else" @prepend_hardline
+)
+
+; Final "else" (not followed by "if", see if_hanging.scm)
+(if_else_expr
+  "else" @append_hardline
+  .
+  [
+    (value_expr)
+    (number_constant_expr)
+    (char_constant_expr)
+    (string_constant_expr)
+    (function_call_expr)
+    (field_access_expr)
+    (operator_as_function_expr)
+    (negate_expr)
+    (bin_op_expr)
+    (parenthesized_expr)
+    (tuple_expr)
+    (list_expr)
+    (record_expr)
+    (case_of_expr)
+    (let_in_expr)
+    (anonymous_function_expr)
+    (glsl_code_expr)
+    (unit_expr)
+  ]
+  .
+)
+
+; Blank line before every "else"
+(if_else_expr
+  (_) @append_delimiter
+  .
+  "else"
+  (#delimiter! "\n")
+)
+
+; Two blank lines between the module doc comment and the first declaration
+; of a module without imports. The comment's own hardline provides the rest.
+(file
+  (module_declaration)
+  .
+  (block_comment) @append_delimiter
+  .
+  [
+    (value_declaration)
+    (type_annotation)
+    (type_declaration)
+    (type_alias_declaration)
+    (port_annotation)
+    (infix_declaration)
+    (block_comment)
+  ]
+  (#delimiter! "__SECTION_COMMENT_DELIMITER__")
+)
+
+; Section comments at the top level: three blank lines before, two after.
+; The comment's own hardline and the declaration delimiter provide the rest.
+(file
+  [
+    (import_clause)
+    (value_declaration)
+    (type_declaration)
+    (type_alias_declaration)
+    (port_annotation)
+    (infix_declaration)
+  ] @append_delimiter
+  .
+  (line_comment)
+  (#delimiter! "\n")
+)
+
+(file
+  (line_comment) @append_delimiter
+  .
+  [
+    (value_declaration)
+    (type_annotation)
+    (type_declaration)
+    (type_alias_declaration)
+    (port_annotation)
+  ]
+  (#delimiter! "__SECTION_COMMENT_DELIMITER__")
+)
+
+; Function calls keep the first argument on the function's line if it was there
+; in the input, also when the call is multi-line: `div []\n    [ ... ]`
+(function_call_expr
+  .
+  (_)
+  .
+  (_) @prepend_input_softline
+)
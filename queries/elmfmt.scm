@@ -0,0 +1,54 @@
+; elmfmt and compact presets
+; Rules of elmfmt's own style that the elm-format preset replaces, see
+; queries/elm_format.scm
+
+; ==============================================================================
+; Top-level comments
+; ==============================================================================
+
+; Preserve blank lines between the module doc comment and a following comment
+(file
+  (module_declaration)
+  .
+  (block_comment)
+  .
+  (block_comment) @allow_blank_line_before
+)
+
+; Preserve blank lines after top-level line comments before declarations
+; Note: This rule allows preserving blank lines from input for regular comments.
+; Section header comments after imports are handled by the rule below.
+(file
+  (line_comment)
+  .
+  [
+    (value_declaration)
+    (type_declaration)
+    (type_alias_declaration)
+    (port_annotation)
+  ] @allow_blank_line_before
+)
+
+; Section header comments after imports get proper spacing.
+; The line_comment already has @append_hardline adding one newline,
+; so we add __SECTION_COMMENT_DELIMITER__ (newlines_between_decls newlines) to get
+; the total spacing matching the configured newlines_between_decls + 1 blank lines.
+; This rule is specifically for line_comment followed by type_annotation at file level.
+(file
+  (line_comment) @append_delimiter
+  .
+  (type_annotation)
+  (#delimiter! "__SECTION_COMMENT_DELIMITER__")
+)
+
+; ==============================================================================
+; Function calls
+; ==============================================================================
+
+; Spaced softline between the function and its first argument
+(function_call_expr
+  .
+  (_)
+  .
+  (_) @prepend_spaced_softline
+)
@@ -51,7 +51,9 @@
   "if" @append_space
 )
 
-; Final "else" (not followed by "if"): spaced softline after and start indent
+; Final "else" (not followed by "if"): spaced softline after and start indent.
+; Anchors skip the anonymous "if", so the expression must also be the last child
+; to keep this from matching an "else if" by its condition.
 (if_else_expr
   "else" @append_spaced_softline @append_indent_start
   .
@@ -75,4 +77,5 @@
     (glsl_code_expr)
     (unit_expr)
   ]
+  .
 )
@@ -0,0 +1,579 @@
+//! The `elmfmt` command line tool
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::num::NonZeroUsize;
+use std::path::{Component, Path, PathBuf};
+use std::process::ExitCode;
+
+use crate::config::config_error;
+use crate::{
+    collect_elm_files, colorize_diff, default_jobs, format_files, in_file, project_source_dirs,
+    unified_diff, ConfigError, ConfigOverrides, Exclusions, FileMessage, FileReport, FileStatus,
+    FormatOptions, Formatter, FormatterCache, FormatterConfig, IdempotenceError, IfStyle,
+    MessageFormat, Mode, RangeUnit, Reporter, SyntaxError, TextRange, TupleStyle, WalkOptions,
+};
+
+/// How a run ended. Each outcome has a documented exit code; when several
+/// apply, the one with the highest code wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    /// Exit code 0: everything is formatted
+    Success,
+    /// Exit code 1: `--check` found files that are not formatted
+    WouldReformat,
+    /// Exit code 2: a file could not be parsed or formatted, or another error
+    Failure,
+    /// Exit code 3: a config file, `elm.json` or command-line value is invalid
+    InvalidConfig,
+}
+
+impl Outcome {
+    /// The outcome of a run that stopped with an error
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        if error.is::<ConfigError>() {
+            Outcome::InvalidConfig
+        } else {
+            Outcome::Failure
+        }
+    }
+
+    /// The outcome of arguments rejected by the parser. Requests for help or
+    /// the version stop parsing too, but succeed.
+    pub fn from_usage_error(error: &clap::Error) -> Self {
+        if error.use_stderr() {
+            Outcome::InvalidConfig
+        } else {
+            Outcome::Success
+        }
+    }
+
+    /// The exit code of the process
+    pub fn code(self) -> u8 {
+        match self {
+            Outcome::Success => 0,
+            Outcome::WouldReformat => 1,
+            Outcome::Failure => 2,
+            Outcome::InvalidConfig => 3,
+        }
+    }
+}
+
+impl From<Outcome> for ExitCode {
+    fn from(outcome: Outcome) -> Self {
+        ExitCode::from(outcome.code())
+    }
+}
+
+/// A formatter for Elm code, powered by Topiary
+#[derive(Parser, Debug)]
+#[command(name = "elmfmt")]
+#[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(after_help = "Exit codes: 0 formatted, 1 would reformat (--check), \
+2 parse or format error, 3 invalid configuration or arguments")]
+pub struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Input files or directories to format. If not provided, reads from stdin, or
+    /// with --check, --in-place or --diff formats the sources listed in elm.json
+    #[arg(value_name = "FILE")]
+    input: Vec<PathBuf>,
+
+    /// Write output to file instead of stdout (only valid with a single input file)
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Modify the file in place
+    #[arg(short, long)]
+    in_place: bool,
+
+    /// Check if the file is already formatted (exit with 1 if not)
+    #[arg(short, long)]
+    check: bool,
+
+    /// Print a unified diff of the changes instead of the formatted code
+    #[arg(short, long)]
+    diff: bool,
+
+    /// How to report results of --check and --in-place runs
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        default_value_t,
+        alias = "format"
+    )]
+    message_format: MessageFormat,
+
+    /// Only format the top-level declarations touching this range (START:END)
+    #[arg(long, value_name = "START:END")]
+    range: Option<String>,
+
+    /// Unit of --range: 1-based inclusive lines or 0-based end-exclusive bytes
+    #[arg(long, value_enum, default_value_t = RangeUnit::Lines, requires = "range")]
+    range_unit: RangeUnit,
+
+    /// Skip paths matching this gitignore-style pattern when walking directories
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+
+    /// Do not descend into symlinked directories or format symlinked files
+    /// when walking directories
+    #[arg(long)]
+    no_follow_symlinks: bool,
+
+    /// Number of files to format in parallel (defaults to the number of CPUs)
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<NonZeroUsize>,
+
+    /// Skip idempotence check
+    #[arg(long)]
+    skip_idempotence: bool,
+
+    /// When the idempotence check fails, write the outputs of both passes to
+    /// this directory, as `<file>.first-pass.elm` and `<file>.second-pass.elm`
+    #[arg(long, value_name = "DIR", conflicts_with = "skip_idempotence")]
+    debug_idempotence: Option<PathBuf>,
+
+    /// Format the declarations of files with syntax errors that parse, keep the
+    /// parts with errors as written and report them
+    #[arg(long)]
+    tolerate_errors: bool,
+
+    /// Number of spaces for indentation (overrides the config file)
+    #[arg(long, value_name = "N")]
+    indent: Option<u8>,
+
+    /// Style for if-then-else expressions (overrides the config file)
+    #[arg(long, value_enum)]
+    if_style: Option<IfStyle>,
+
+    /// Style for tuples (overrides the config file)
+    #[arg(long, value_enum)]
+    tuple_style: Option<TupleStyle>,
+
+    /// Blank lines between top-level declarations (overrides the config file)
+    #[arg(long, value_name = "N")]
+    newlines_between_decls: Option<u8>,
+
+    /// Print the configuration that applies to the input (or the current directory) and exit
+    #[arg(long)]
+    print_config: bool,
+
+    /// Read from stdin (for compatibility with elm-format)
+    #[arg(long)]
+    stdin: bool,
+
+    /// Elm version (ignored, for compatibility with elm-format)
+    #[arg(long, value_name = "VERSION")]
+    elm_version: Option<String>,
+
+    /// Auto-confirm (ignored, for compatibility with elm-format)
+    #[arg(long)]
+    yes: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a Language Server Protocol server over stdin/stdout
+    Lsp,
+}
+
+/// Print a unified diff to stdout, coloured when attached to a terminal
+fn print_diff(path: &str, original: &str, formatted: &str) -> Result<()> {
+    let diff = unified_diff(path, original, formatted);
+    let mut stdout = io::stdout();
+    let diff = if stdout.is_terminal() && std::env::var_os("NO_COLOR").is_none() {
+        colorize_diff(&diff)
+    } else {
+        diff
+    };
+    stdout
+        .write_all(diff.as_bytes())
+        .context("Failed to write to stdout")
+}
+
+/// Write the outputs of both passes of an idempotence failure of a file to
+/// `dir`, keeping the path of the file below it
+fn write_idempotence_passes(dir: &Path, path: &Path, error: &anyhow::Error) -> Result<()> {
+    let Some(error) = error.downcast_ref::<IdempotenceError>() else {
+        return Ok(());
+    };
+    let relative: PathBuf = path
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect();
+    let base = dir.join(relative);
+    if let Some(parent) = base.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    let passes = [
+        (base.with_extension("first-pass.elm"), Some(&error.first)),
+        (
+            base.with_extension("second-pass.elm"),
+            error.second.as_ref(),
+        ),
+    ];
+    for (pass_path, output) in passes {
+        if let Some(output) = output {
+            fs::write(&pass_path, output)
+                .with_context(|| format!("Failed to write file: {}", pass_path.display()))?;
+            eprintln!("Wrote: {}", pass_path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Run the command line tool. Errors that stop the run map to an outcome with
+/// [`Outcome::from_error`].
+pub fn run(args: Args) -> Result<Outcome> {
+    if let Some(Command::Lsp) = args.command {
+        crate::lsp::run_stdio()?;
+        return Ok(Outcome::Success);
+    }
+    let overrides = ConfigOverrides {
+        indentation: args.indent,
+        if_style: args.if_style,
+        tuple_style: args.tuple_style,
+        newlines_between_decls: args.newlines_between_decls,
+    };
+    if args.print_config {
+        let config_dir = match args.input.first() {
+            Some(path) if path.is_dir() => Some(path.as_path()),
+            Some(path) => path.parent(),
+            None => None,
+        };
+        let resolved = FormatterConfig::resolve_with_overrides(config_dir, &overrides)
+            .map_err(config_error)?;
+        print!("{}", resolved.to_annotated_yaml()?);
+        return Ok(Outcome::Success);
+    }
+    // Machine-readable reports replace the human output on stdout and stderr
+    let machine = args.message_format != MessageFormat::Human;
+    if machine {
+        let format = args.message_format.to_possible_value().unwrap();
+        if !args.check && !args.in_place {
+            anyhow::bail!(
+                "--message-format {} requires --check or --in-place",
+                format.get_name()
+            );
+        }
+        if args.diff {
+            anyhow::bail!(
+                "Cannot use --diff with --message-format {}",
+                format.get_name()
+            );
+        }
+    }
+    let options = FormatOptions {
+        skip_idempotence: args.skip_idempotence,
+        tolerate_errors: args.tolerate_errors,
+    };
+
+    // Without paths, --check, --in-place and --diff format the sources of the
+    // Elm project in the current directory
+    let mut inputs = args.input.clone();
+    let mut project_mode = false;
+    if inputs.is_empty() && !args.stdin && (args.check || args.in_place || args.diff) {
+        if let Some(dirs) = project_source_dirs(None).map_err(config_error)? {
+            // Report paths relative to the current directory
+            let cwd = std::env::current_dir()?;
+            inputs = dirs
+                .into_iter()
+                .filter(|dir| dir.is_dir())
+                .map(|dir| dir.strip_prefix(&cwd).map_or(dir.clone(), PathBuf::from))
+                .collect();
+            project_mode = true;
+        }
+    }
+
+    // Determine if we're in multi-file mode (multiple inputs or any directories)
+    let has_multiple_inputs = inputs.len() > 1;
+    let has_directory = !args.stdin && inputs.iter().any(|p| p.is_dir());
+    let multi_file_mode = !args.stdin && (project_mode || has_multiple_inputs || has_directory);
+
+    if multi_file_mode {
+        // Multi-file mode: format all specified files and directories
+        if args.output.is_some() {
+            anyhow::bail!("Cannot use --output with multiple inputs or directories");
+        }
+        if args.range.is_some() {
+            anyhow::bail!("Cannot use --range with multiple inputs or directories");
+        }
+        if !args.in_place && !args.check && !args.diff {
+            anyhow::bail!(
+                "When formatting multiple files or directories, you must use --in-place, --check or --diff"
+            );
+        }
+        let mode = if args.in_place && !args.check {
+            Mode::InPlace
+        } else {
+            Mode::Check
+        };
+
+        let walk_options = WalkOptions {
+            exclusions: Exclusions::new(&args.exclude).map_err(config_error)?,
+            follow_symlinks: !args.no_follow_symlinks,
+        };
+        let found = collect_elm_files(&inputs, &walk_options)?;
+        for warning in &found.warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        let files = found.files;
+        if files.is_empty() {
+            eprintln!("No .elm files found");
+            return Ok(Outcome::Success);
+        }
+
+        let mut needs_formatting = false;
+        let mut errors: Vec<(PathBuf, anyhow::Error)> = Vec::new();
+        let mut formatters = FormatterCache::with_overrides(overrides);
+        let jobs = args.jobs.unwrap_or_else(default_jobs);
+        let results = format_files(&files, &mut formatters, mode, &options, jobs);
+
+        // Results are in path order, so the report is deterministic
+        let mut reporter = Reporter::new(args.message_format, io::stdout().lock());
+        for (file, result) in files.iter().zip(results) {
+            if machine {
+                reporter.report(FileMessage::new(file, &result, mode))?;
+            }
+            let report = match result {
+                Ok(report) => report,
+                Err(e) => {
+                    errors.push((file.clone(), e));
+                    continue;
+                }
+            };
+            if let FileStatus::Changed { .. } = report.status {
+                needs_formatting = true;
+            }
+            if machine {
+                continue;
+            }
+            for warning in &report.warnings {
+                eprintln!("Warning: {}:{}", file.display(), warning);
+            }
+            match report.status {
+                FileStatus::Unchanged => {}
+                FileStatus::Changed {
+                    original,
+                    formatted,
+                } => {
+                    match mode {
+                        Mode::Check => eprintln!("Would reformat: {}", file.display()),
+                        Mode::InPlace => eprintln!("Formatted: {}", file.display()),
+                    }
+                    if args.diff {
+                        print_diff(&file.display().to_string(), &original, &formatted)?;
+                    }
+                }
+            }
+        }
+
+        reporter.finish()?;
+
+        if let Some(dir) = &args.debug_idempotence {
+            for (file, err) in &errors {
+                write_idempotence_passes(dir, file, err)?;
+            }
+        }
+
+        let mut outcome = if args.check && needs_formatting {
+            Outcome::WouldReformat
+        } else {
+            Outcome::Success
+        };
+        for (_, err) in &errors {
+            outcome = outcome.max(Outcome::from_error(err));
+        }
+        if !errors.is_empty() && !machine {
+            eprintln!();
+            for (file, err) in &errors {
+                if err.is::<SyntaxError>() {
+                    eprintln!("Error: {}", err);
+                } else {
+                    eprintln!("Error: {}: {:#}", file.display(), err);
+                }
+            }
+            eprintln!();
+            eprintln!("{} file(s) failed to format", errors.len());
+        }
+        Ok(outcome)
+    } else {
+        // Single file or stdin mode
+        let single_input = inputs.first();
+        let config_search_dir = single_input.and_then(|p| p.parent());
+        let config = FormatterConfig::load_with_overrides(config_search_dir, &overrides)
+            .map_err(config_error)?;
+
+        // Read input (--stdin flag takes precedence over input file)
+        let input_content = match single_input {
+            Some(path) if !args.stdin => fs::read_to_string(path)
+                .with_context(|| format!("Failed to read file: {}", path.display()))?,
+            _ => {
+                let mut buffer = String::new();
+                io::stdin()
+                    .read_to_string(&mut buffer)
+                    .context("Failed to read from stdin")?;
+                buffer
+            }
+        };
+
+        let label = match single_input {
+            Some(path) if !args.stdin => path.display().to_string(),
+            _ => "<stdin>".to_string(),
+        };
+
+        let formatter = Formatter::new(&config).map_err(config_error)?;
+        let formatted = match args.range {
+            Some(ref range) => {
+                let range = TextRange::parse(range, args.range_unit).map_err(config_error)?;
+                formatter.format_range_with_options(&input_content, &range, &options)
+            }
+            None => formatter.format_with_options(&input_content, &options),
+        }
+        .map_err(|e| in_file(e, Path::new(&label)));
+        if let (Some(dir), Err(e)) = (&args.debug_idempotence, &formatted) {
+            let path = match single_input {
+                Some(path) if !args.stdin => path.as_path(),
+                _ => Path::new("stdin.elm"),
+            };
+            write_idempotence_passes(dir, path, e)?;
+        }
+        let mut warnings = formatter.warnings(&input_content)?;
+        if options.tolerate_errors {
+            warnings.extend(formatter.syntax_errors(&input_content)?);
+        }
+
+        if machine {
+            let mode = if args.in_place && !args.check {
+                Mode::InPlace
+            } else {
+                Mode::Check
+            };
+            let result = formatted.map(|formatted| FileReport {
+                status: if formatted == input_content {
+                    FileStatus::Unchanged
+                } else {
+                    FileStatus::Changed {
+                        original: input_content,
+                        formatted,
+                    }
+                },
+                warnings,
+            });
+            if let (Mode::InPlace, Ok(report)) = (mode, &result) {
+                if let FileStatus::Changed { formatted, .. } = &report.status {
+                    let Some(path) = single_input.filter(|_| !args.stdin) else {
+                        anyhow::bail!("Cannot use --in-place without an input file");
+                    };
+                    fs::write(path, formatted)
+                        .with_context(|| format!("Failed to write file: {}", path.display()))?;
+                }
+            }
+            let mut reporter = Reporter::new(args.message_format, io::stdout().lock());
+            reporter.report(FileMessage::new(Path::new(&label), &result, mode))?;
+            reporter.finish()?;
+            return Ok(match &result {
+                Ok(report) if mode == Mode::Check && report.status != FileStatus::Unchanged => {
+                    Outcome::WouldReformat
+                }
+                Ok(_) => Outcome::Success,
+                Err(e) => Outcome::from_error(e),
+            });
+        }
+
+        let formatted = formatted?;
+        for warning in warnings {
+            eprintln!("Warning: {}:{}", label, warning);
+        }
+        if args.diff {
+            print_diff(&label, &input_content, &formatted)?;
+        }
+
+        // Handle check mode
+        if args.check {
+            if formatted != input_content {
+                eprintln!("File would be reformatted");
+                return Ok(Outcome::WouldReformat);
+            }
+            return Ok(Outcome::Success);
+        }
+
+        // Write output
+        if args.in_place {
+            if let Some(path) = single_input {
+                fs::write(path, &formatted)
+                    .with_context(|| format!("Failed to write file: {}", path.display()))?;
+            } else {
+                anyhow::bail!("Cannot use --in-place without an input file");
+            }
+        } else if let Some(ref path) = args.output {
+            fs::write(path, &formatted)
+                .with_context(|| format!("Failed to write file: {}", path.display()))?;
+        } else if !args.diff {
+            // With --diff, the diff replaces the formatted output on stdout
+            io::stdout()
+                .write_all(formatted.as_bytes())
+                .context("Failed to write to stdout")?;
+        }
+        Ok(Outcome::Success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::config_error;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_outcomes_of_errors() {
+        assert_eq!(Outcome::from_error(&anyhow!("boom")), Outcome::Failure);
+        let error = config_error(anyhow!("bad key"));
+        assert_eq!(Outcome::from_error(&error), Outcome::InvalidConfig);
+        assert_eq!(error.to_string(), "bad key");
+        assert_eq!(
+            Outcome::WouldReformat.max(Outcome::Failure).code(),
+            Outcome::Failure.code()
+        );
+        assert_eq!(Outcome::InvalidConfig.code(), 3);
+    }
+
+    #[test]
+    fn test_outcomes_of_usage_errors() {
+        let outcome = |args: &[&str]| {
+            let error = Args::try_parse_from([&["elmfmt"], args].concat()).unwrap_err();
+            Outcome::from_usage_error(&error)
+        };
+        assert_eq!(outcome(&["--indent", "abc"]), Outcome::InvalidConfig);
+        assert_eq!(outcome(&["--if-style", "foo"]), Outcome::InvalidConfig);
+        assert_eq!(outcome(&["--help"]), Outcome::Success);
+        assert_eq!(outcome(&["--version"]), Outcome::Success);
+    }
+
+    #[test]
+    fn test_idempotence_passes_are_written_below_the_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = anyhow::Error::new(IdempotenceError {
+            first: "a =\n    1\n".to_string(),
+            second: Some("a = 1\n".to_string()),
+        })
+        .context("Failed to format: /project/src/Main.elm");
+        write_idempotence_passes(dir.path(), Path::new("/project/src/Main.elm"), &error).unwrap();
+        let base = dir.path().join("project/src");
+        assert_eq!(
+            fs::read_to_string(base.join("Main.first-pass.elm")).unwrap(),
+            "a =\n    1\n"
+        );
+        assert_eq!(
+            fs::read_to_string(base.join("Main.second-pass.elm")).unwrap(),
+            "a = 1\n"
+        );
+    }
+}
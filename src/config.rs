@@ -0,0 +1,929 @@
+use anyhow::{anyhow, Context, Result};
+use serde::de::{self, DeserializeSeed, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use crate::files::build_matcher;
+use crate::project::{self, ELM_JSON_FILE_NAME};
+
+/// Configuration file name
+pub const CONFIG_FILE_NAME: &str = "elmfmt.yaml";
+
+/// Configuration file names, in order of precedence within a directory
+pub const CONFIG_FILE_NAMES: &[&str] = &[CONFIG_FILE_NAME, ".elmfmt.yaml", "elmfmt.toml"];
+
+/// Default number of spaces for indentation
+const DEFAULT_INDENT_SPACES: u8 = 2;
+
+/// Default number of newlines between top-level declarations
+const DEFAULT_NEWLINES_BETWEEN_DECLS: u8 = 2;
+
+/// Style for if-then-else expressions
+#[derive(
+    Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, Default, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum IfStyle {
+    /// Indented style (default)
+    ///
+    /// ```elm
+    /// if condition
+    ///   then expr1
+    ///   else expr2
+    /// ```
+    #[default]
+    Indented,
+    /// Hanging style
+    ///
+    /// ```elm
+    /// if condition then
+    ///     expr1
+    /// else
+    ///     expr2
+    /// ```
+    Hanging,
+}
+
+/// Style for tuple expressions
+#[derive(
+    Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, Default, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum TupleStyle {
+    /// Compact style (no spaces inside parentheses)
+    ///
+    /// ```elm
+    /// (a, b)
+    /// ```
+    Compact,
+    /// Spaced style (default, elm-format compatible)
+    ///
+    /// ```elm
+    /// ( a, b )
+    /// ```
+    #[default]
+    Spaced,
+}
+
+/// A named bundle of query files and option defaults. Options set next to the
+/// preset override its defaults.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset {
+    /// elmfmt's own style (default)
+    #[default]
+    Elmfmt,
+    /// Reproduce the output of elm-format: 4-space indentation, hanging if,
+    /// spaced tuples, case branches on their own lines, sorted imports and the
+    /// module `exposing` list following the `@docs` lines
+    ElmFormat,
+    /// elmfmt's style with less whitespace: compact tuples and a single blank
+    /// line between declarations
+    Compact,
+}
+
+/// Keys of `elmfmt.yaml` that select the preset. `style` is the older name.
+const PRESET_KEYS: &[&str] = &["preset", "style"];
+
+/// Key of `elmfmt.yaml` that stops the search for config files in parent directories
+const ROOT_KEY: &str = "root";
+
+/// All keys of `elmfmt.yaml`
+const KEYS: &[&str] = &[
+    ROOT_KEY,
+    "preset",
+    "style",
+    "indentation",
+    "if-style",
+    "tuple-style",
+    "newlines-between-decls",
+    "sort-imports",
+    "sort-exposing",
+    "exposing-follows-docs",
+    "extra-queries",
+    "exclude",
+];
+
+/// Allowed values of `indentation`
+pub(crate) const INDENTATION_RANGE: RangeInclusive<u8> = 1..=8;
+
+/// Allowed values of `newlines-between-decls`
+pub(crate) const NEWLINES_BETWEEN_DECLS_RANGE: RangeInclusive<u8> = 1..=4;
+
+/// Configuration for the formatter, as read from `elmfmt.yaml`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct FormatterConfig {
+    /// Preset providing the queries and the defaults for the other options
+    #[serde(alias = "style")]
+    pub preset: Preset,
+    /// Number of spaces to use for indentation
+    pub indentation: u8,
+    /// Style for if-then-else expressions
+    pub if_style: IfStyle,
+    /// Style for tuple expressions
+    pub tuple_style: TupleStyle,
+    /// Number of blank lines between top-level declarations
+    pub newlines_between_decls: u8,
+    /// Sort imports alphabetically by module name and merge duplicates
+    pub sort_imports: bool,
+    /// Sort `exposing` lists (types, values, operators) and remove duplicates
+    pub sort_exposing: bool,
+    /// Order the module `exposing` list by the `@docs` lines of the module documentation
+    pub exposing_follows_docs: bool,
+    /// Topiary query files appended to the built-in queries, relative to the config file
+    pub extra_queries: Vec<PathBuf>,
+    /// Gitignore-style patterns of paths that directory walks skip, relative to
+    /// the config file
+    pub exclude: Vec<String>,
+}
+
+impl Default for FormatterConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatterConfig {
+    pub fn new() -> Self {
+        Self {
+            preset: Preset::default(),
+            indentation: DEFAULT_INDENT_SPACES,
+            if_style: IfStyle::default(),
+            tuple_style: TupleStyle::default(),
+            newlines_between_decls: DEFAULT_NEWLINES_BETWEEN_DECLS,
+            sort_imports: false,
+            sort_exposing: false,
+            exposing_follows_docs: false,
+            extra_queries: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    /// The default configuration of a preset
+    pub fn for_preset(preset: Preset) -> Self {
+        match preset {
+            Preset::Elmfmt => Self::new(),
+            Preset::ElmFormat => Self {
+                preset,
+                indentation: 4,
+                if_style: IfStyle::Hanging,
+                tuple_style: TupleStyle::Spaced,
+                newlines_between_decls: 2,
+                sort_imports: true,
+                exposing_follows_docs: true,
+                ..Self::new()
+            },
+            Preset::Compact => Self {
+                preset,
+                tuple_style: TupleStyle::Compact,
+                newlines_between_decls: 1,
+                ..Self::new()
+            },
+        }
+    }
+
+    /// Parse a configuration from the contents of an `elmfmt.yaml` file.
+    /// Missing keys fall back to the defaults of the configured `preset`.
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        Self::from_layers(&[parse_keys(content)?])
+    }
+
+    /// Build a configuration from the keys set by config files, outermost
+    /// first: keys of later files override those of earlier ones, and missing
+    /// keys fall back to the defaults of the last preset set.
+    fn from_layers(layers: &[Mapping]) -> Result<Self> {
+        let preset = match layers.iter().rev().find_map(preset_value) {
+            Some(value) => serde_yaml::from_value(value.clone())?,
+            None => Preset::default(),
+        };
+
+        let mut merged = serde_yaml::to_value(Self::for_preset(preset))?;
+        for (key, value) in layers.iter().flatten() {
+            if !is_search_key(key) {
+                merged[key] = value.clone();
+            }
+        }
+        Ok(serde_yaml::from_value(merged)?)
+    }
+
+    /// The configuration as YAML, in the format of `elmfmt.yaml`
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    /// Load configuration from config files, searching from the given
+    /// directory upward. See [`FormatterConfig::resolve`].
+    pub fn load(start_dir: Option<&Path>) -> Result<Self> {
+        Self::load_with_overrides(start_dir, &ConfigOverrides::default())
+    }
+
+    /// Load configuration from config files and apply command-line overrides
+    pub fn load_with_overrides(
+        start_dir: Option<&Path>,
+        overrides: &ConfigOverrides,
+    ) -> Result<Self> {
+        Ok(Self::resolve_with_overrides(start_dir, overrides)?.config)
+    }
+
+    /// Merge the config files (elmfmt.yaml, .elmfmt.yaml, elmfmt.toml and the
+    /// `"elmfmt"` object of elm.json) found from the given directory (or the
+    /// current directory) upward. Files closer to the start directory override
+    /// keys of files further up; the search stops at a directory with a file
+    /// containing `root: true`. Without config files, this is the default
+    /// configuration.
+    pub fn resolve(start_dir: Option<&Path>) -> Result<ResolvedConfig> {
+        Self::resolve_with_overrides(start_dir, &ConfigOverrides::default())
+    }
+
+    /// [`FormatterConfig::resolve`], with command-line overrides applied last
+    pub fn resolve_with_overrides(
+        start_dir: Option<&Path>,
+        overrides: &ConfigOverrides,
+    ) -> Result<ResolvedConfig> {
+        let mut layers = Vec::new();
+        for dir in search_start(start_dir).ancestors() {
+            // Within a directory, earlier names override later ones
+            let mut is_root = false;
+            let names = CONFIG_FILE_NAMES.iter().chain([&ELM_JSON_FILE_NAME]);
+            for path in names.map(|name| dir.join(name)) {
+                let Some(keys) = read_config_keys(&path)? else {
+                    continue;
+                };
+                is_root |= keys.get(ROOT_KEY).and_then(Value::as_bool) == Some(true);
+                layers.push((ConfigSource::File(path), keys));
+            }
+            if is_root {
+                break;
+            }
+        }
+        layers.reverse();
+        layers.push((ConfigSource::CommandLine, overrides.keys()?));
+
+        let keys: Vec<Mapping> = layers.iter().map(|(_, keys)| keys.clone()).collect();
+        let mut config = Self::from_layers(&keys)?;
+
+        let mut sources = BTreeMap::new();
+        for (source, keys) in &layers {
+            for key in keys.keys().filter_map(Value::as_str) {
+                match key {
+                    ROOT_KEY => {}
+                    key if PRESET_KEYS.contains(&key) => {
+                        sources.insert("preset".to_string(), source.clone());
+                    }
+                    key => {
+                        sources.insert(key.to_string(), source.clone());
+                    }
+                }
+            }
+        }
+
+        // Relative paths are relative to the file that set them
+        if let Some(ConfigSource::File(path)) = sources.get("extra-queries") {
+            if let Some(dir) = path.parent() {
+                config.resolve_paths(dir);
+            }
+        }
+
+        Ok(ResolvedConfig {
+            config,
+            files: layers
+                .into_iter()
+                .filter_map(|(source, _)| match source {
+                    ConfigSource::File(path) => Some(path),
+                    ConfigSource::CommandLine => None,
+                })
+                .collect(),
+            sources,
+        })
+    }
+
+    /// Make relative paths in the configuration relative to `dir`
+    fn resolve_paths(&mut self, dir: &Path) {
+        for path in &mut self.extra_queries {
+            if path.is_relative() {
+                *path = dir.join(&*path);
+            }
+        }
+    }
+
+    /// Check the values that config files range-check, for configurations
+    /// built in code. Errors are [`ConfigError`]s.
+    pub(crate) fn validate(&self) -> Result<()> {
+        for (key, value, range) in [
+            ("indentation", self.indentation, INDENTATION_RANGE),
+            (
+                "newlines-between-decls",
+                self.newlines_between_decls,
+                NEWLINES_BETWEEN_DECLS_RANGE,
+            ),
+        ] {
+            if !range.contains(&value) {
+                return Err(config_error(anyhow!(
+                    "invalid value for `{}`: {}, expected a number from {} to {}",
+                    key,
+                    value,
+                    range.start(),
+                    range.end()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the indentation string based on configuration
+    pub(crate) fn indent_string(&self) -> String {
+        " ".repeat(self.indentation as usize)
+    }
+
+    /// Get the delimiter string for newlines between declarations (escaped for query syntax).
+    /// The config value represents blank lines, so we add 1 for the line-ending newline.
+    pub(crate) fn decl_delimiter(&self) -> String {
+        "\\n".repeat(self.newlines_between_decls as usize + 1)
+    }
+
+    /// Get the delimiter string for section comments (one less newline than decl_delimiter,
+    /// since line_comment already has @append_hardline adding one newline)
+    pub(crate) fn section_comment_delimiter(&self) -> String {
+        "\\n".repeat(self.newlines_between_decls as usize)
+    }
+}
+
+/// A merged configuration and the config files it came from
+#[derive(Debug, Clone)]
+pub struct ResolvedConfig {
+    pub config: FormatterConfig,
+    /// The merged config files, outermost first
+    pub files: Vec<PathBuf>,
+    /// Where each key was set. Keys not listed have the default of the preset.
+    pub sources: BTreeMap<String, ConfigSource>,
+}
+
+/// Where a configuration value was set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    File(PathBuf),
+    CommandLine,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigSource::File(path) => write!(f, "{}", path.display()),
+            ConfigSource::CommandLine => f.write_str("command line"),
+        }
+    }
+}
+
+/// Options given on the command line, overriding those of config files
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ConfigOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub indentation: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub if_style: Option<IfStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tuple_style: Option<TupleStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub newlines_between_decls: Option<u8>,
+}
+
+impl ConfigOverrides {
+    /// The overridden keys, checked like those of a config file
+    fn keys(&self) -> Result<Mapping> {
+        let Value::Mapping(overrides) = serde_yaml::to_value(self)? else {
+            return Ok(Mapping::new());
+        };
+        let mut keys = Mapping::new();
+        for (key, value) in overrides {
+            let entry = Mapping::from_iter([(key.clone(), value)]);
+            let checked = ConfigKeys::deserialize(Value::Mapping(entry)).with_context(|| {
+                format!(
+                    "Invalid command-line value for `{}`",
+                    key.as_str().unwrap_or_default()
+                )
+            })?;
+            keys.extend(checked.0);
+        }
+        Ok(keys)
+    }
+}
+
+impl ResolvedConfig {
+    /// The configuration as YAML, with a comment after each key naming the
+    /// file it came from
+    pub fn to_annotated_yaml(&self) -> Result<String> {
+        let mut yaml = String::new();
+        if self.files.is_empty() {
+            yaml.push_str("# No config file found\n");
+        }
+        for path in &self.files {
+            yaml.push_str(&format!("# Merged {}\n", path.display()));
+        }
+        for line in self.config.to_yaml()?.lines() {
+            yaml.push_str(line);
+            // Top-level keys start at the beginning of the line
+            if let Some((key, _)) = line
+                .split_once(':')
+                .filter(|_| !line.starts_with([' ', '-']))
+            {
+                match self.sources.get(key) {
+                    Some(source) => yaml.push_str(&format!("  # {source}")),
+                    None => yaml.push_str("  # default"),
+                }
+            }
+            yaml.push('\n');
+        }
+        Ok(yaml)
+    }
+}
+
+/// The keys set by a config file
+fn parse_keys(content: &str) -> Result<Mapping> {
+    // An empty file is valid YAML for "no settings"
+    if content.trim().is_empty() {
+        return Ok(Mapping::new());
+    }
+    Ok(serde_yaml::from_str::<ConfigKeys>(content)?.0)
+}
+
+/// The keys set by a config file. Deserializing checks every key and value, so
+/// errors carry the position of the offending entry.
+#[derive(Debug)]
+pub(crate) struct ConfigKeys(pub(crate) Mapping);
+
+impl<'de> Deserialize<'de> for ConfigKeys {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ConfigKeysVisitor)
+    }
+}
+
+struct ConfigKeysVisitor;
+
+impl<'de> Visitor<'de> for ConfigKeysVisitor {
+    type Value = ConfigKeys;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a mapping of configuration keys")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<ConfigKeys, E> {
+        Ok(ConfigKeys(Mapping::new()))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ConfigKeys, A::Error> {
+        let mut keys = Mapping::new();
+        while let Some(Key(key)) = map.next_key()? {
+            let value = match key {
+                "preset" | "style" => serde_yaml::to_value(map.next_value::<Preset>()?),
+                "indentation" => Ok(map.next_value_seed(InRange(INDENTATION_RANGE))?.into()),
+                "if-style" => serde_yaml::to_value(map.next_value::<IfStyle>()?),
+                "tuple-style" => serde_yaml::to_value(map.next_value::<TupleStyle>()?),
+                "newlines-between-decls" => Ok(map
+                    .next_value_seed(InRange(NEWLINES_BETWEEN_DECLS_RANGE))?
+                    .into()),
+                "extra-queries" => serde_yaml::to_value(map.next_value::<Vec<PathBuf>>()?),
+                "exclude" => {
+                    let patterns: Vec<String> = map.next_value()?;
+                    build_matcher(Path::new(""), &patterns)
+                        .map_err(|e| de::Error::custom(format!("{e:#}")))?;
+                    serde_yaml::to_value(patterns)
+                }
+                // root and the sort options
+                _ => Ok(map.next_value::<bool>()?.into()),
+            };
+            keys.insert(key.into(), value.map_err(de::Error::custom)?);
+        }
+        Ok(ConfigKeys(keys))
+    }
+}
+
+/// A known key of a config file
+struct Key(&'static str);
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(KeyVisitor)
+    }
+}
+
+struct KeyVisitor;
+
+impl Visitor<'_> for KeyVisitor {
+    type Value = Key;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a configuration key")
+    }
+
+    fn visit_str<E: de::Error>(self, key: &str) -> Result<Key, E> {
+        match KEYS.iter().find(|known| **known == key) {
+            Some(known) => Ok(Key(known)),
+            None => Err(E::custom(unknown_key_message(key))),
+        }
+    }
+}
+
+/// The error for an unknown key, suggesting the most similar known key
+fn unknown_key_message(key: &str) -> String {
+    let closest = KEYS
+        .iter()
+        .map(|known| (strsim::jaro_winkler(key, known), known))
+        .filter(|(similarity, _)| *similarity > 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0));
+    match closest {
+        Some((_, known)) => format!("unknown key `{key}`, did you mean `{known}`?"),
+        None => format!("unknown key `{key}`"),
+    }
+}
+
+/// Deserializes a number and checks that it is in the range
+struct InRange(RangeInclusive<u8>);
+
+impl<'de> DeserializeSeed<'de> for InRange {
+    type Value = u8;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<u8, D::Error> {
+        deserializer.deserialize_u8(self)
+    }
+}
+
+impl Visitor<'_> for InRange {
+    type Value = u8;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a number from {} to {}", self.0.start(), self.0.end())
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<u8, E> {
+        match u8::try_from(value) {
+            Ok(value) if self.0.contains(&value) => Ok(value),
+            _ => Err(E::invalid_value(de::Unexpected::Unsigned(value), &self)),
+        }
+    }
+
+    // TOML integers are signed
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<u8, E> {
+        match u64::try_from(value) {
+            Ok(value) => self.visit_u64(value),
+            Err(_) => Err(E::invalid_value(de::Unexpected::Signed(value), &self)),
+        }
+    }
+}
+
+/// An invalid configuration: a config file, `elm.json`, an `--exclude` pattern
+/// or a command-line value. Displays the whole chain of the wrapped error.
+#[derive(Debug)]
+pub struct ConfigError(pub anyhow::Error);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Mark an error as a [`ConfigError`]
+pub(crate) fn config_error(error: anyhow::Error) -> anyhow::Error {
+    if error.is::<ConfigError>() {
+        error
+    } else {
+        ConfigError(error).into()
+    }
+}
+
+/// An error at a position of a file, as `path:line:column: message`.
+/// `message` may end in the " at line L column C" that serde errors append.
+pub(crate) fn located_error(
+    path: &Path,
+    message: &str,
+    line: usize,
+    column: usize,
+) -> anyhow::Error {
+    let message = message
+        .strip_suffix(&format!(" at line {line} column {column}"))
+        .unwrap_or(message);
+    anyhow!("{}:{line}:{column}: {message}", path.display())
+}
+
+fn preset_key(key: &Value) -> bool {
+    key.as_str().is_some_and(|key| PRESET_KEYS.contains(&key))
+}
+
+/// Keys that control how config files are found and merged, rather than formatting
+fn is_search_key(key: &Value) -> bool {
+    preset_key(key) || key.as_str() == Some(ROOT_KEY)
+}
+
+/// The preset selected by a config file, if any
+fn preset_value(keys: &Mapping) -> Option<&Value> {
+    PRESET_KEYS.iter().find_map(|key| keys.get(*key))
+}
+
+/// The directory the search for config files starts in: the given directory,
+/// or the current directory
+pub(crate) fn search_start(start_dir: Option<&Path>) -> PathBuf {
+    let start = start_dir
+        .filter(|p| !p.as_os_str().is_empty())
+        .map(|p| p.to_path_buf())
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_else(|| PathBuf::from("."));
+    // Relative paths would stop the search at the current directory
+    std::path::absolute(&start).unwrap_or(start)
+}
+
+/// The keys set by a config file, `None` if it does not exist or, for
+/// `elm.json`, has no `"elmfmt"` object
+fn read_config_keys(path: &Path) -> Result<Option<Mapping>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    if path.file_name() == Some(ELM_JSON_FILE_NAME.as_ref()) {
+        return project::config_keys(path);
+    }
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    if path.extension().is_some_and(|ext| ext == "toml") {
+        return match toml::from_str::<ConfigKeys>(&content) {
+            Ok(keys) => Ok(Some(keys.0)),
+            Err(e) => {
+                let offset = e.span().map_or(0, |span| span.start);
+                let line_start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+                let line = content[..offset].matches('\n').count() + 1;
+                let column = content[line_start..offset].chars().count() + 1;
+                Err(located_error(path, e.message(), line, column))
+            }
+        };
+    }
+    parse_keys(&content).map(Some).map_err(|e| {
+        match e
+            .downcast_ref::<serde_yaml::Error>()
+            .and_then(|e| Some((e, e.location()?)))
+        {
+            Some((e, location)) => {
+                located_error(path, &e.to_string(), location.line(), location.column())
+            }
+            None => e.context(format!("Failed to parse config file: {}", path.display())),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yaml_keys_are_kebab_case() {
+        let config = FormatterConfig::from_yaml(
+            "indentation: 4\nif-style: hanging\ntuple-style: compact\nnewlines-between-decls: 1\n",
+        )
+        .unwrap();
+        assert_eq!(config.indentation, 4);
+        assert_eq!(config.if_style, IfStyle::Hanging);
+        assert_eq!(config.tuple_style, TupleStyle::Compact);
+        assert_eq!(config.newlines_between_decls, 1);
+    }
+
+    #[test]
+    fn test_missing_keys_use_defaults() {
+        let config = FormatterConfig::from_yaml("if-style: hanging\n").unwrap();
+        assert_eq!(config.indentation, DEFAULT_INDENT_SPACES);
+        assert_eq!(
+            config.newlines_between_decls,
+            DEFAULT_NEWLINES_BETWEEN_DECLS
+        );
+        assert_eq!(
+            FormatterConfig::from_yaml("").unwrap(),
+            FormatterConfig::new()
+        );
+    }
+
+    #[test]
+    fn test_preset_sets_defaults_of_other_keys() {
+        let config = FormatterConfig::from_yaml("preset: elm-format\n").unwrap();
+        assert_eq!(config, FormatterConfig::for_preset(Preset::ElmFormat));
+        assert_eq!(config.indentation, 4);
+        assert_eq!(config.if_style, IfStyle::Hanging);
+
+        let config = FormatterConfig::from_yaml("preset: compact\nindentation: 4\n").unwrap();
+        assert_eq!(config.indentation, 4);
+        assert_eq!(config.tuple_style, TupleStyle::Compact);
+        assert_eq!(config.newlines_between_decls, 1);
+
+        let config = FormatterConfig::from_yaml("style: elm-format\n").unwrap();
+        assert_eq!(config.preset, Preset::ElmFormat);
+    }
+
+    #[test]
+    fn test_yaml_round_trip() {
+        let config = FormatterConfig::from_yaml("preset: elm-format\nindentation: 2\n").unwrap();
+        assert_eq!(
+            FormatterConfig::from_yaml(&config.to_yaml().unwrap()).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn test_extra_queries_are_relative_to_the_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(CONFIG_FILE_NAME),
+            "extra-queries:\n  - queries/team.scm\n",
+        )
+        .unwrap();
+        let config = FormatterConfig::load(Some(dir.path())).unwrap();
+        assert_eq!(
+            config.extra_queries,
+            vec![dir.path().join("queries/team.scm")]
+        );
+    }
+
+    #[test]
+    fn test_nested_configs_are_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().join("packages/app");
+        fs::create_dir_all(&package).unwrap();
+        fs::write(
+            dir.path().join(CONFIG_FILE_NAME),
+            "preset: elm-format\nindentation: 8\n",
+        )
+        .unwrap();
+        fs::write(package.join(CONFIG_FILE_NAME), "indentation: 2\n").unwrap();
+
+        let resolved = FormatterConfig::resolve(Some(&package)).unwrap();
+        assert_eq!(resolved.config.preset, Preset::ElmFormat);
+        assert_eq!(resolved.config.indentation, 2);
+        assert_eq!(resolved.config.if_style, IfStyle::Hanging);
+        assert_eq!(
+            resolved.sources["preset"],
+            ConfigSource::File(dir.path().join(CONFIG_FILE_NAME))
+        );
+        assert_eq!(
+            resolved.sources["indentation"],
+            ConfigSource::File(package.join(CONFIG_FILE_NAME))
+        );
+        assert!(!resolved.sources.contains_key("if-style"));
+    }
+
+    #[test]
+    fn test_root_stops_the_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().join("package");
+        fs::create_dir_all(&package).unwrap();
+        fs::write(dir.path().join(CONFIG_FILE_NAME), "indentation: 8\n").unwrap();
+        fs::write(
+            package.join(CONFIG_FILE_NAME),
+            "root: true\nif-style: hanging\n",
+        )
+        .unwrap();
+
+        let resolved = FormatterConfig::resolve(Some(&package)).unwrap();
+        assert_eq!(resolved.files, vec![package.join(CONFIG_FILE_NAME)]);
+        assert_eq!(resolved.config.indentation, DEFAULT_INDENT_SPACES);
+        assert_eq!(resolved.config.if_style, IfStyle::Hanging);
+    }
+
+    #[test]
+    fn test_elm_json_settings_are_overridden_by_elmfmt_yaml() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(ELM_JSON_FILE_NAME),
+            r#"{ "type": "application", "elmfmt": { "indentation": 4, "if-style": "hanging" } }"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join(CONFIG_FILE_NAME), "indentation: 8\n").unwrap();
+
+        let resolved = FormatterConfig::resolve(Some(dir.path())).unwrap();
+        assert_eq!(
+            resolved.files,
+            vec![
+                dir.path().join(ELM_JSON_FILE_NAME),
+                dir.path().join(CONFIG_FILE_NAME)
+            ]
+        );
+        assert_eq!(resolved.config.indentation, 8);
+        assert_eq!(resolved.config.if_style, IfStyle::Hanging);
+        assert_eq!(
+            resolved.sources["if-style"],
+            ConfigSource::File(dir.path().join(ELM_JSON_FILE_NAME))
+        );
+    }
+
+    #[test]
+    fn test_unknown_keys_suggest_the_closest_key() {
+        let error = FormatterConfig::from_yaml("indentation: 4\nif_style: hanging\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown key `if_style`, did you mean `if-style`? at line 2 column 1"
+        );
+        let error = FormatterConfig::from_yaml("root: true\ncolour: blue\n").unwrap_err();
+        assert_eq!(error.to_string(), "unknown key `colour` at line 2 column 1");
+    }
+
+    #[test]
+    fn test_out_of_range_values_are_rejected() {
+        for (yaml, column) in [
+            ("indentation: 0\n", 14),
+            ("newlines-between-decls: 255\n", 25),
+        ] {
+            let error = FormatterConfig::from_yaml(yaml).unwrap_err();
+            let location = error
+                .downcast_ref::<serde_yaml::Error>()
+                .and_then(serde_yaml::Error::location)
+                .unwrap();
+            assert_eq!((location.line(), location.column()), (1, column));
+        }
+    }
+
+    #[test]
+    fn test_config_file_errors_point_to_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        fs::write(&path, "sort-imports: true\nindentation: 99\n").unwrap();
+        let error = FormatterConfig::resolve(Some(dir.path())).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "{}:2:14: indentation: invalid value: integer `99`, expected a number from 1 to 8",
+                path.display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_toml_and_dotfile_configs() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("elmfmt.toml"),
+            "indentation = 4\ntuple-style = \"compact\"\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join(".elmfmt.yaml"), "indentation: 3\n").unwrap();
+
+        let config = FormatterConfig::load(Some(dir.path())).unwrap();
+        assert_eq!(config.indentation, 3);
+        assert_eq!(config.tuple_style, TupleStyle::Compact);
+
+        fs::write(
+            dir.path().join("elmfmt.toml"),
+            "sort-imports = true\nindentation = 0\n",
+        )
+        .unwrap();
+        let error = FormatterConfig::load(Some(dir.path())).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "{}:2:15: invalid value: integer `0`, expected a number from 1 to 8",
+                dir.path().join("elmfmt.toml").display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_command_line_overrides_config_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(CONFIG_FILE_NAME),
+            "indentation: 4\nif-style: hanging\n",
+        )
+        .unwrap();
+        let overrides = ConfigOverrides {
+            indentation: Some(8),
+            ..ConfigOverrides::default()
+        };
+
+        let resolved =
+            FormatterConfig::resolve_with_overrides(Some(dir.path()), &overrides).unwrap();
+        assert_eq!(resolved.config.indentation, 8);
+        assert_eq!(resolved.config.if_style, IfStyle::Hanging);
+        assert_eq!(resolved.sources["indentation"], ConfigSource::CommandLine);
+
+        let overrides = ConfigOverrides {
+            newlines_between_decls: Some(0),
+            ..ConfigOverrides::default()
+        };
+        assert!(FormatterConfig::load_with_overrides(Some(dir.path()), &overrides).is_err());
+    }
+
+    #[test]
+    fn test_configs_built_in_code_are_range_checked() {
+        assert!(FormatterConfig::new().validate().is_ok());
+        let config = FormatterConfig {
+            newlines_between_decls: 255,
+            ..FormatterConfig::new()
+        };
+        let error = config.validate().unwrap_err();
+        assert!(error.is::<ConfigError>());
+        assert_eq!(
+            error.to_string(),
+            "invalid value for `newlines-between-decls`: 255, expected a number from 1 to 4"
+        );
+    }
+}
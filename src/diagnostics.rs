@@ -0,0 +1,227 @@
+//! Diagnostics for Elm code that does not parse
+
+use anyhow::Result;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tree_sitter::Node;
+
+use crate::diff::labelled_diff;
+use crate::syntax::{error_nodes, parse};
+
+/// Opening brackets and the node kinds they start
+const BRACKETS: &[(&str, &str, &str)] = &[
+    ("(", ")", "parenthesized_expr"),
+    ("[", "]", "list_expr"),
+    ("{", "}", "record_expr"),
+];
+
+/// A syntax error in Elm code, located at its first `ERROR` or `MISSING` node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// The file the code came from, if known
+    pub path: Option<PathBuf>,
+    /// 1-based line
+    pub line: usize,
+    /// 1-based column, in bytes
+    pub column: usize,
+    /// What is wrong, derived from the surrounding nodes
+    pub message: String,
+    /// The line of code containing the error
+    pub source_line: String,
+}
+
+impl SyntaxError {
+    /// The first syntax error in the code, if any
+    pub(crate) fn find(content: &str) -> Result<Option<Self>> {
+        let tree = parse(content)?;
+        let Some(node) = error_nodes(tree.root_node()).into_iter().next() else {
+            return Ok(None);
+        };
+        Ok(Some(Self::at(content, node)))
+    }
+
+    /// The syntax error of an `ERROR` or `MISSING` node
+    pub(crate) fn at(content: &str, node: Node) -> Self {
+        let (node, message) = diagnose(content, node);
+        let position = node.start_position();
+        Self {
+            path: None,
+            line: position.row + 1,
+            column: position.column + 1,
+            message,
+            source_line: content.lines().nth(position.row).unwrap_or("").to_string(),
+        }
+    }
+
+    /// The same error, reported for a file
+    pub fn with_path(self, path: &Path) -> Self {
+        Self {
+            path: Some(path.to_path_buf()),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        writeln!(
+            f,
+            "{}:{}: syntax error: {}",
+            self.line, self.column, self.message
+        )?;
+
+        // Keep tabs so the caret lines up with the code
+        let before = self
+            .source_line
+            .get(..self.column - 1)
+            .unwrap_or(&self.source_line);
+        let indent: String = before
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let gutter = " ".repeat(self.line.to_string().len());
+        writeln!(f, "{gutter} |")?;
+        writeln!(f, "{} | {}", self.line, self.source_line)?;
+        write!(f, "{gutter} | {indent}^")
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Formatting the formatted output again changes it, which is a bug in the
+/// formatting queries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotenceError {
+    /// The output of the first pass
+    pub first: String,
+    /// The output of formatting the first pass again, or `None` if the first
+    /// pass does not parse
+    pub second: Option<String>,
+}
+
+impl fmt::Display for IdempotenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.second {
+            Some(second) => write!(
+                f,
+                "Formatting is not idempotent, a second pass changes the output:\n{}",
+                labelled_diff("first pass", "second pass", &self.first, second).trim_end()
+            ),
+            None => write!(
+                f,
+                "Formatting is not idempotent, the output of the first pass does not parse"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IdempotenceError {}
+
+/// Report a formatting error for a file: syntax errors get the path in their
+/// location, other errors get it as context
+pub fn in_file(error: anyhow::Error, path: &Path) -> anyhow::Error {
+    match error.downcast::<SyntaxError>() {
+        Ok(error) => error.with_path(path).into(),
+        Err(error) => error.context(format!("Failed to format: {}", path.display())),
+    }
+}
+
+/// Where an `ERROR` or `MISSING` node goes wrong, with a short description
+/// like "unterminated record_expr"
+pub(crate) fn diagnose<'tree>(content: &str, node: Node<'tree>) -> (Node<'tree>, String) {
+    let parent = node
+        .parent()
+        .filter(|parent| parent.kind() != "file")
+        .map(|parent| parent.kind());
+
+    if node.is_missing() {
+        if let Some((_, _, kind)) = BRACKETS.iter().find(|(_, close, _)| *close == node.kind()) {
+            return (node, format!("unterminated {}", parent.unwrap_or(kind)));
+        }
+        let message = match parent {
+            Some(parent) => format!("missing `{}` in {}", node.kind(), parent),
+            None => format!("missing `{}`", node.kind()),
+        };
+        return (node, message);
+    }
+
+    // An opening bracket without its closing bracket
+    let mut open: Vec<(Node, &str)> = Vec::new();
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if let Some((_, _, kind)) = BRACKETS.iter().find(|(o, _, _)| *o == child.kind()) {
+            open.push((child, kind));
+        } else if let Some(index) = BRACKETS.iter().position(|(_, c, _)| *c == child.kind()) {
+            if let Some(last) = open
+                .iter()
+                .rposition(|(_, kind)| *kind == BRACKETS[index].2)
+            {
+                open.truncate(last);
+            }
+        }
+    }
+    if let Some((bracket, kind)) = open.first() {
+        return (*bracket, format!("unterminated {kind}"));
+    }
+
+    let unexpected = content[node.byte_range()]
+        .split_whitespace()
+        .next()
+        .unwrap_or("");
+    let message = match (unexpected, parent) {
+        ("", Some(parent)) => format!("unexpected end of {parent}"),
+        ("", None) => "unexpected end of input".to_string(),
+        (token, Some(parent)) => format!("unexpected `{token}` in {parent}"),
+        (token, None) => format!("unexpected `{token}`"),
+    };
+    (node, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unterminated_record() {
+        let error = SyntaxError::find("module Main exposing (..)\n\n\nmodel =\n    { count = 1\n")
+            .unwrap()
+            .unwrap()
+            .with_path(Path::new("src/Main.elm"));
+        assert_eq!(error.message, "unterminated record_expr");
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "src/Main.elm:{}:{}: syntax error: unterminated record_expr\n  |\n{} | {}\n  | {}^",
+                error.line,
+                error.column,
+                error.line,
+                error.source_line,
+                " ".repeat(error.column - 1)
+            )
+        );
+    }
+
+    #[test]
+    fn test_idempotence_error_shows_both_passes() {
+        let error = IdempotenceError {
+            first: "a =\n    1\n".to_string(),
+            second: Some("a = 1\n".to_string()),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Formatting is not idempotent, a second pass changes the output:\n\
+             --- first pass\n+++ second pass\n@@ -1,2 +1 @@\n-a =\n-    1\n+a = 1"
+        );
+    }
+
+    #[test]
+    fn test_valid_code_has_no_error() {
+        assert_eq!(
+            SyntaxError::find("module Main exposing (..)\n\n\na = 1\n").unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use similar::{DiffTag, TextDiff};
+use std::ops::RangeInclusive;
+
+/// Number of unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Render a unified diff between the original and formatted content.
+/// `path` is used for both the `---` and `+++` file headers.
+/// Returns an empty string if the contents are equal.
+pub fn unified_diff(path: &str, original: &str, formatted: &str) -> String {
+    labelled_diff(path, path, original, formatted)
+}
+
+/// Render a unified diff with different `---` and `+++` headers
+pub(crate) fn labelled_diff(old_label: &str, new_label: &str, old: &str, new: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .header(old_label, new_label)
+        .to_string()
+}
+
+/// The 1-based, inclusive line ranges of the original content that formatting
+/// changes. Inserted lines are attributed to the line they are inserted before,
+/// or to the last line at the end of the content.
+pub fn changed_lines(original: &str, formatted: &str) -> Vec<RangeInclusive<usize>> {
+    let diff = TextDiff::from_lines(original, formatted);
+    let last_line = diff.old_slices().len().max(1);
+    let mut ranges: Vec<RangeInclusive<usize>> = Vec::new();
+    for op in diff.ops() {
+        let (tag, old, _) = op.as_tag_tuple();
+        if tag == DiffTag::Equal {
+            continue;
+        }
+        let start = (old.start + 1).min(last_line);
+        let end = old.end.max(start);
+        match ranges.last_mut() {
+            Some(last) if *last.end() + 1 >= start => *last = *last.start()..=end.max(*last.end()),
+            _ => ranges.push(start..=end),
+        }
+    }
+    ranges
+}
+
+/// Add ANSI colours to a unified diff for terminal output
+pub fn colorize_diff(diff: &str) -> String {
+    let mut out = String::with_capacity(diff.len());
+    for line in diff.split_inclusive('\n') {
+        let color = if line.starts_with("---") || line.starts_with("+++") {
+            BOLD
+        } else if line.starts_with("@@") {
+            CYAN
+        } else if line.starts_with('-') {
+            RED
+        } else if line.starts_with('+') {
+            GREEN
+        } else {
+            out.push_str(line);
+            continue;
+        };
+        let (text, newline) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+        out.push_str(color);
+        out.push_str(text);
+        out.push_str(RESET);
+        out.push_str(newline);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff_has_headers_and_hunks() {
+        let diff = unified_diff("src/Main.elm", "a = 1\nb=2\n", "a = 1\nb = 2\n");
+        assert_eq!(
+            diff,
+            "--- src/Main.elm\n+++ src/Main.elm\n@@ -1,2 +1,2 @@\n a = 1\n-b=2\n+b = 2\n"
+        );
+        assert_eq!(unified_diff("src/Main.elm", "a\n", "a\n"), "");
+    }
+
+    #[test]
+    fn test_changed_lines_are_ranges_of_the_original() {
+        assert_eq!(
+            changed_lines(
+                "a = 1\nb=2\nc=3\nd = 4\ne=5\n",
+                "a = 1\nb = 2\nc = 3\nd = 4\ne = 5\n"
+            ),
+            vec![2..=3, 5..=5]
+        );
+        assert_eq!(changed_lines("a = 1\n", "a = 1\n\n\nb = 2\n"), vec![1..=1]);
+        assert!(changed_lines("a = 1\n", "a = 1\n").is_empty());
+    }
+
+    #[test]
+    fn test_colorize_diff_keeps_context_lines_plain() {
+        let colored = colorize_diff(" a = 1\n-b=2\n+b = 2\n");
+        assert_eq!(
+            colored,
+            format!(" a = 1\n{RED}-b=2{RESET}\n{GREEN}+b = 2{RESET}\n")
+        );
+    }
+}
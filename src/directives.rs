@@ -0,0 +1,170 @@
+//! `-- elmfmt: off` / `-- elmfmt: on` and `-- elmfmt: skip` comments, which
+//! keep top-level parts of a module exactly as written
+
+use anyhow::{bail, Result};
+use std::borrow::Cow;
+use std::ops::Range;
+use tree_sitter::Node;
+
+use crate::syntax::{is_comment, named_children, parse, text};
+
+/// A directive comment of elmfmt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Directive {
+    /// Keep everything up to the next `-- elmfmt: on` (or the end of the module)
+    Off,
+    On,
+    /// Keep the next declaration, with its type annotation
+    Skip,
+}
+
+pub(crate) fn directive(comment: &str) -> Option<Directive> {
+    let body = comment.strip_prefix("--")?.trim();
+    match body.strip_prefix("elmfmt:")?.trim() {
+        "off" => Some(Directive::Off),
+        "on" => Some(Directive::On),
+        "skip" => Some(Directive::Skip),
+        _ => None,
+    }
+}
+
+/// Byte ranges of the regions to keep verbatim, in order. Each starts at its
+/// directive comment; only top-level comments count.
+fn verbatim_regions(content: &str, root: Node) -> Vec<Range<usize>> {
+    let children: Vec<Node> = named_children(root).collect();
+    let mut regions = Vec::new();
+    let mut off_start = None;
+    let mut covered_until = 0;
+    for (i, node) in children.iter().enumerate() {
+        if node.kind() != "line_comment" || node.start_byte() < covered_until {
+            continue;
+        }
+        match (directive(text(content, *node)), off_start) {
+            (Some(Directive::Off), None) => off_start = Some(node.start_byte()),
+            (Some(Directive::On), Some(start)) => {
+                // Keep the spacing after the comment too, as the formatter would
+                // attach it to the next declaration
+                let end = children
+                    .get(i + 1)
+                    .map_or(node.end_byte(), Node::start_byte);
+                regions.push(start..end);
+                covered_until = end;
+                off_start = None;
+            }
+            (Some(Directive::Skip), None) => {
+                let mut decls = children[i + 1..].iter().filter(|n| !is_comment(n));
+                let Some(decl) = decls.next() else {
+                    continue;
+                };
+                let end = match decls.next() {
+                    Some(value)
+                        if decl.kind() == "type_annotation"
+                            && value.kind() == "value_declaration" =>
+                    {
+                        value.end_byte()
+                    }
+                    _ => decl.end_byte(),
+                };
+                regions.push(node.start_byte()..end);
+                covered_until = end;
+            }
+            _ => {}
+        }
+    }
+    if let (Some(start), Some(last)) = (off_start, children.last()) {
+        regions.push(start..last.end_byte());
+    }
+    regions
+}
+
+/// The directive comment of a region and the first two words of the code
+/// after it, which formatting does not change
+fn region_start(region: &str) -> (&str, Vec<&str>) {
+    let (comment, code) = region.split_once('\n').unwrap_or((region, ""));
+    let words = code
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+        .filter(|word| !word.is_empty())
+        .take(2)
+        .collect();
+    (comment.trim_end(), words)
+}
+
+/// Put the regions protected by directive comments in `original` back into
+/// its formatted version
+pub(crate) fn restore_verbatim<'a>(original: &str, formatted: &'a str) -> Result<Cow<'a, str>> {
+    if !original.contains("elmfmt:") {
+        return Ok(Cow::Borrowed(formatted));
+    }
+    let original_tree = parse(original)?;
+    let original_regions = verbatim_regions(original, original_tree.root_node());
+    if original_regions.is_empty() {
+        return Ok(Cow::Borrowed(formatted));
+    }
+    let formatted_tree = parse(formatted)?;
+    let formatted_regions = verbatim_regions(formatted, formatted_tree.root_node());
+    if formatted_regions.len() != original_regions.len() {
+        bail!("Formatting moved an elmfmt directive comment");
+    }
+
+    let mut result = String::with_capacity(formatted.len());
+    let mut copied_until = 0;
+    for (kept, replaced) in original_regions.into_iter().zip(formatted_regions) {
+        // Regions are matched by position, so each must start with the same
+        // directive comment and the same code after it
+        if region_start(&original[kept.clone()]) != region_start(&formatted[replaced.clone()]) {
+            bail!("Formatting moved an elmfmt directive comment");
+        }
+        result.push_str(&formatted[copied_until..replaced.start]);
+        result.push_str(&original[kept]);
+        copied_until = replaced.end;
+    }
+    result.push_str(&formatted[copied_until..]);
+    Ok(Cow::Owned(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_off_region_is_kept() {
+        let original = "module Main exposing (..)\n\n\n-- elmfmt: off\ntable =\n    [ ( 1,   \"one\" )\n    , ( 10,  \"ten\" )\n    ]\n-- elmfmt: on\n\n\nx=1\n";
+        let formatted = "module Main exposing (..)\n\n\n-- elmfmt: off\ntable =\n  [ (1, \"one\"), (10, \"ten\") ]\n-- elmfmt: on\nx = 1\n";
+        assert_eq!(
+            restore_verbatim(original, formatted).unwrap(),
+            "module Main exposing (..)\n\n\n-- elmfmt: off\ntable =\n    [ ( 1,   \"one\" )\n    , ( 10,  \"ten\" )\n    ]\n-- elmfmt: on\n\n\nx = 1\n"
+        );
+    }
+
+    #[test]
+    fn test_skip_keeps_the_annotated_declaration() {
+        let original = "module Main exposing (..)\n\n\n-- elmfmt: skip\nf : Int\nf =  1\n\n\ng=2\n";
+        let formatted =
+            "module Main exposing (..)\n\n\n-- elmfmt: skip\nf : Int\nf =\n  1\n\n\ng = 2\n";
+        assert_eq!(
+            restore_verbatim(original, formatted).unwrap(),
+            "module Main exposing (..)\n\n\n-- elmfmt: skip\nf : Int\nf =  1\n\n\ng = 2\n"
+        );
+    }
+
+    #[test]
+    fn test_regions_that_moved_are_rejected() {
+        let original = "module Main exposing (..)\n\nimport Set\n-- elmfmt: off\nimport Task\n-- elmfmt: on\nimport Array\n";
+        let formatted = "module Main exposing (..)\n\nimport Array\n-- elmfmt: off\nimport Set\n-- elmfmt: on\nimport Task\n";
+        let error = restore_verbatim(original, formatted).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Formatting moved an elmfmt directive comment"
+        );
+    }
+
+    #[test]
+    fn test_unclosed_off_region_runs_to_the_end() {
+        let original = "module Main exposing (..)\n\n\nx=1\n\n\n-- elmfmt: off\ny =  2\n";
+        let formatted = "module Main exposing (..)\n\n\nx = 1\n\n\n-- elmfmt: off\ny = 2\n";
+        assert_eq!(
+            restore_verbatim(original, formatted).unwrap(),
+            "module Main exposing (..)\n\n\nx = 1\n\n\n-- elmfmt: off\ny =  2\n"
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Module `exposing` lists that follow the `@docs` lines of the module
+//! documentation, like elm-format (`exposing-follows-docs`)
+
+use anyhow::Result;
+use std::borrow::Cow;
+use std::ops::Range;
+use tree_sitter::Node;
+
+use crate::exposing::{exposed_items, ExposedItem};
+use crate::syntax::{has_comment, named_children, parse, text};
+use crate::Warning;
+
+/// The module `exposing` list matched against the `@docs` lines
+struct DocsLayout<'a> {
+    /// Byte range of the module's `exposing_list`
+    list_range: Range<usize>,
+    /// Exposed items grouped by `@docs` line, followed by undocumented items
+    groups: Vec<Vec<&'a str>>,
+    /// Exposed items that are not listed in any `@docs` line
+    undocumented: Vec<Warning>,
+}
+
+/// Strip the parentheses of an operator, so `(|=)` in `@docs` matches `|=`
+fn doc_name(name: &str) -> &str {
+    name.trim()
+        .strip_prefix('(')
+        .and_then(|n| n.strip_suffix(')'))
+        .map_or(name.trim(), str::trim)
+}
+
+/// The names on each `@docs` line of a doc comment
+fn docs_lines(doc_comment: &str) -> Vec<Vec<&str>> {
+    doc_comment
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix("@docs"))
+        .map(|names| {
+            names
+                .split(',')
+                .map(doc_name)
+                .filter(|name| !name.is_empty())
+                .collect()
+        })
+        .collect()
+}
+
+/// The module documentation: a `{-|` comment directly after the module declaration
+fn module_doc<'tree>(module: Node<'tree>) -> Option<Node<'tree>> {
+    let mut node = module.next_named_sibling()?;
+    while node.kind() == "line_comment" {
+        node = node.next_named_sibling()?;
+    }
+    (node.kind() == "block_comment").then_some(node)
+}
+
+fn analyze<'a>(content: &'a str, root: Node) -> Option<DocsLayout<'a>> {
+    let module = named_children(root).find(|n| n.kind() == "module_declaration")?;
+    let list = module.child_by_field_name("exposing")?;
+    if has_comment(list) {
+        return None;
+    }
+    let doc = module_doc(module)?;
+    let doc_text = text(content, doc);
+    if !doc_text.starts_with("{-|") {
+        return None;
+    }
+    let lines = docs_lines(doc_text);
+    if lines.is_empty() {
+        return None;
+    }
+
+    let items = exposed_items(content, list)?;
+    let item_nodes: Vec<Node> = named_children(list)
+        .filter(|n| n.kind().starts_with("exposed_"))
+        .collect();
+    let mut remaining: Vec<Option<&ExposedItem>> = items.iter().map(Some).collect();
+
+    let mut groups: Vec<Vec<&str>> = Vec::new();
+    for names in lines {
+        let group: Vec<&str> = names
+            .iter()
+            .filter_map(|name| {
+                let slot = remaining
+                    .iter_mut()
+                    .find(|slot| slot.is_some_and(|item| doc_name(item.name) == *name))?;
+                slot.take().map(|item| item.text)
+            })
+            .collect();
+        if !group.is_empty() {
+            groups.push(group);
+        }
+    }
+
+    let mut undocumented = Vec::new();
+    let mut rest = Vec::new();
+    for (item, node) in remaining.iter().zip(&item_nodes) {
+        if let Some(item) = item {
+            rest.push(item.text);
+            let position = node.start_position();
+            undocumented.push(Warning {
+                line: position.row + 1,
+                column: position.column + 1,
+                message: format!(
+                    "`{}` is exposed but not listed in any @docs line",
+                    item.name
+                ),
+            });
+        }
+    }
+    if !rest.is_empty() {
+        groups.push(rest);
+    }
+
+    Some(DocsLayout {
+        list_range: list.byte_range(),
+        groups,
+        undocumented,
+    })
+}
+
+/// Exposed names of the module that are not listed in any `@docs` line.
+/// Modules without `@docs` lines in their documentation produce no warnings.
+pub(crate) fn undocumented_exposed(content: &str) -> Result<Vec<Warning>> {
+    let tree = parse(content)?;
+    Ok(analyze(content, tree.root_node())
+        .map(|layout| layout.undocumented)
+        .unwrap_or_default())
+}
+
+/// Rewrite the module `exposing` list of formatted code so that it follows the
+/// `@docs` lines: one line per `@docs` line, in the same order, with
+/// undocumented names on a final line.
+pub(crate) fn layout_exposing<'a>(formatted: &'a str, indent: &str) -> Result<Cow<'a, str>> {
+    let tree = parse(formatted)?;
+    let root = tree.root_node();
+    if root.has_error() {
+        return Ok(Cow::Borrowed(formatted));
+    }
+    let Some(layout) = analyze(formatted, root) else {
+        return Ok(Cow::Borrowed(formatted));
+    };
+
+    let lines: Vec<String> = layout.groups.iter().map(|group| group.join(", ")).collect();
+    let list = if lines.len() == 1 {
+        format!("exposing ({})", lines[0])
+    } else {
+        format!(
+            "exposing\n{indent}( {}\n{indent})",
+            lines.join(&format!("\n{indent}, "))
+        )
+    };
+
+    let mut result = formatted.to_string();
+    result.replace_range(layout.list_range, &list);
+    Ok(Cow::Owned(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULE: &str = "module Parser exposing (run, Parser, (|=), helper, int)\n\n{-| Parsers\n\n@docs Parser, run\n@docs int, (|=)\n\n-}\n";
+
+    #[test]
+    fn test_exposing_follows_docs_lines() {
+        assert_eq!(
+            layout_exposing(MODULE, "    ").unwrap(),
+            "module Parser exposing\n    ( Parser, run\n    , int, (|=)\n    , helper\n    )\n\n{-| Parsers\n\n@docs Parser, run\n@docs int, (|=)\n\n-}\n"
+        );
+    }
+
+    #[test]
+    fn test_reports_undocumented_names() {
+        let warnings = undocumented_exposed(MODULE).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].line, warnings[0].column), (1, 44));
+        assert!(warnings[0].message.contains("`helper`"));
+    }
+
+    #[test]
+    fn test_modules_without_docs_are_unchanged() {
+        let input = "module Main exposing (b, a)\n\n{-| No docs lines -}\n";
+        assert_eq!(layout_exposing(input, "  ").unwrap(), input);
+        assert!(undocumented_exposed(input).unwrap().is_empty());
+    }
+}
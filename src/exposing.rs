@@ -0,0 +1,145 @@
+//! Sorting and normalization of `exposing` lists (`sort-exposing`)
+
+use anyhow::Result;
+use std::borrow::Cow;
+use tree_sitter::Node;
+
+use crate::syntax::{has_comment, named_children, parse, text};
+
+/// Groups of exposed items, in the order they are sorted into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ExposedKind {
+    Type,
+    Value,
+    Operator,
+}
+
+/// An entry of an `exposing` list
+pub(crate) struct ExposedItem<'a> {
+    kind: ExposedKind,
+    pub(crate) name: &'a str,
+    pub(crate) text: &'a str,
+    /// Whether a type is exposed with its constructors, `Type(..)`
+    constructors: bool,
+}
+
+impl<'a> ExposedItem<'a> {
+    fn new(content: &'a str, node: Node) -> Option<Self> {
+        let kind = match node.kind() {
+            "exposed_type" => ExposedKind::Type,
+            "exposed_value" => ExposedKind::Value,
+            "exposed_operator" => ExposedKind::Operator,
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            name: node
+                .named_child(0)
+                .map_or(text(content, node), |n| text(content, n)),
+            text: text(content, node),
+            constructors: named_children(node).any(|n| n.kind() == "exposed_union_constructors"),
+        })
+    }
+}
+
+/// The items of an `exposing` list, or `None` for `exposing (..)`
+pub(crate) fn exposed_items<'a>(content: &'a str, list: Node) -> Option<Vec<ExposedItem<'a>>> {
+    if list.child_by_field_name("doubleDot").is_some() {
+        return None;
+    }
+    Some(
+        named_children(list)
+            .filter_map(|node| ExposedItem::new(content, node))
+            .collect(),
+    )
+}
+
+/// Add an item unless one with the same name is present; `Type(..)` replaces `Type`
+pub(crate) fn add_unique<'a>(items: &mut Vec<ExposedItem<'a>>, item: ExposedItem<'a>) {
+    match items.iter_mut().find(|existing| existing.name == item.name) {
+        Some(existing) => {
+            if item.constructors && !existing.constructors {
+                *existing = item;
+            }
+        }
+        None => items.push(item),
+    }
+}
+
+/// Sort every `exposing` list of the module header and imports: types, then
+/// values, then operators, alphabetically within each group and without
+/// duplicates.
+///
+/// Lists containing comments and `exposing (..)` are left alone. Inputs that do
+/// not parse are returned unchanged so the formatter can report the error.
+pub(crate) fn sort_exposing(content: &str) -> Result<Cow<'_, str>> {
+    let tree = parse(content)?;
+    let root = tree.root_node();
+    if root.has_error() {
+        return Ok(Cow::Borrowed(content));
+    }
+
+    let lists = named_children(root)
+        .filter(|node| matches!(node.kind(), "module_declaration" | "import_clause"))
+        .filter_map(|node| node.child_by_field_name("exposing"))
+        .filter(|list| !has_comment(*list));
+
+    let mut replacements = Vec::new();
+    for list in lists {
+        let Some(items) = exposed_items(content, list) else {
+            continue;
+        };
+        let (Some(first), Some(last)) = (
+            named_children(list).find(|n| n.kind().starts_with("exposed_")),
+            named_children(list)
+                .filter(|n| n.kind().starts_with("exposed_"))
+                .last(),
+        ) else {
+            continue;
+        };
+
+        let mut unique = Vec::new();
+        for item in items {
+            add_unique(&mut unique, item);
+        }
+        unique.sort_by(|a, b| (a.kind, a.name).cmp(&(b.kind, b.name)));
+
+        // Keep multi-line lists multi-line
+        let separator = if list.start_position().row == list.end_position().row {
+            ", "
+        } else {
+            "\n, "
+        };
+        let sorted: Vec<&str> = unique.iter().map(|item| item.text).collect();
+        replacements.push((first.start_byte()..last.end_byte(), sorted.join(separator)));
+    }
+
+    if replacements.is_empty() {
+        return Ok(Cow::Borrowed(content));
+    }
+    let mut result = content.to_string();
+    for (range, replacement) in replacements.into_iter().rev() {
+        result.replace_range(range, &replacement);
+    }
+    Ok(Cow::Owned(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sorts_types_values_operators() {
+        let input = "module Main exposing (view, (|=), Model, init, Msg(..), Msg, view)\n\nimport Parser exposing ((|.), Parser, run)\n";
+        assert_eq!(
+            sort_exposing(input).unwrap(),
+            "module Main exposing (Model, Msg(..), init, view, (|=))\n\nimport Parser exposing (Parser, run, (|.))\n"
+        );
+    }
+
+    #[test]
+    fn test_keeps_expose_all_and_commented_lists() {
+        let input = "module Main exposing (..)\n\nimport Html exposing (text {- why -}, div)\n";
+        assert_eq!(sort_exposing(input).unwrap(), input);
+    }
+}
@@ -0,0 +1,504 @@
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::WalkBuilder;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::config::config_error;
+use crate::{
+    in_file, ConfigOverrides, ConfigSource, FormatOptions, Formatter, FormatterConfig, Warning,
+};
+
+/// What to do with a file whose formatted output differs from its content
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Only report whether the file would be reformatted
+    Check,
+    /// Overwrite the file with the formatted output
+    InPlace,
+}
+
+/// Result of processing a single file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileStatus {
+    /// The file was already formatted
+    Unchanged,
+    /// The file is not formatted (and was rewritten in [`Mode::InPlace`])
+    Changed { original: String, formatted: String },
+}
+
+/// Outcome of processing a single file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReport {
+    pub status: FileStatus,
+    pub warnings: Vec<Warning>,
+}
+
+/// Ignore file listing paths that directory walks skip, in `.gitignore` syntax
+pub const IGNORE_FILE_NAME: &str = ".elmfmtignore";
+
+/// Directories that never contain sources to format
+const SKIPPED_DIRS: &[&str] = &[".git", "elm-stuff"];
+
+/// Gitignore-style patterns that exclude paths from directory walks: the
+/// `--exclude` patterns and the `exclude` patterns of config files
+#[derive(Debug, Clone, Default)]
+pub struct Exclusions {
+    /// `--exclude` patterns, relative to the current directory
+    command_line: Option<Arc<Gitignore>>,
+    /// The `exclude` patterns that apply in each directory, relative to the
+    /// config file that set them
+    configs: Arc<Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>>,
+}
+
+impl Exclusions {
+    /// Exclusions with additional patterns, relative to the current directory
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        if patterns.is_empty() {
+            return Ok(Self::default());
+        }
+        let root = std::env::current_dir()?;
+        Ok(Self {
+            command_line: Some(Arc::new(build_matcher(&root, patterns)?)),
+            ..Self::default()
+        })
+    }
+
+    /// Whether a path found by a directory walk is excluded
+    fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        let Ok(path) = std::path::absolute(path) else {
+            return false;
+        };
+        let matches = |matcher: &Gitignore| {
+            path.starts_with(matcher.path()) && matcher.matched(&path, is_dir).is_ignore()
+        };
+        if self.command_line.as_deref().is_some_and(matches) {
+            return true;
+        }
+        path.parent()
+            .and_then(|dir| self.config_matcher(dir))
+            .is_some_and(|matcher| matches(&matcher))
+    }
+
+    /// The matcher for the `exclude` patterns of the config of a directory
+    fn config_matcher(&self, dir: &Path) -> Option<Arc<Gitignore>> {
+        let mut configs = self.configs.lock().unwrap_or_else(|e| e.into_inner());
+        configs
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                // Config errors are reported when the files are formatted
+                let resolved = FormatterConfig::resolve(Some(dir)).ok()?;
+                let Some(ConfigSource::File(file)) = resolved.sources.get("exclude") else {
+                    return None;
+                };
+                build_matcher(file.parent()?, &resolved.config.exclude)
+                    .ok()
+                    .map(Arc::new)
+            })
+            .clone()
+    }
+}
+
+/// Compile gitignore-style patterns relative to `root`
+pub(crate) fn build_matcher(root: &Path, patterns: &[String]) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+    for pattern in patterns {
+        builder
+            .add_line(None, pattern)
+            .with_context(|| format!("Invalid exclude pattern: {pattern}"))?;
+    }
+    Ok(builder.build()?)
+}
+
+/// How directories are walked to find .elm files
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    pub exclusions: Exclusions,
+    /// Descend into symlinked directories and format symlinked files
+    pub follow_symlinks: bool,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            exclusions: Exclusions::default(),
+            follow_symlinks: true,
+        }
+    }
+}
+
+/// The .elm files found in the inputs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FoundFiles {
+    /// Sorted, each file only once even if reachable through several paths
+    pub files: Vec<PathBuf>,
+    /// Problems that did not stop the search, like symlink loops
+    pub warnings: Vec<String>,
+}
+
+impl FoundFiles {
+    /// Sort the paths and keep the first of the paths leading to the same file
+    fn new(mut paths: Vec<PathBuf>, warnings: Vec<String>) -> Self {
+        paths.sort();
+        let mut seen = HashSet::new();
+        paths.retain(|path| seen.insert(fs::canonicalize(path).unwrap_or_else(|_| path.clone())));
+        Self {
+            files: paths,
+            warnings,
+        }
+    }
+}
+
+/// The ancestor and child paths of a symlink loop error
+fn symlink_loop(err: &ignore::Error) -> Option<(&Path, &Path)> {
+    match err {
+        ignore::Error::Loop { ancestor, child } => Some((ancestor, child)),
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => symlink_loop(err),
+        _ => None,
+    }
+}
+
+/// Find all .elm files in a directory recursively, in path order. Skips
+/// `elm-stuff`, paths listed in `.gitignore` and `.elmfmtignore` files, and the
+/// exclusions. Symlink loops are reported as warnings.
+pub fn find_elm_files(dir: &Path, options: &WalkOptions) -> Result<FoundFiles> {
+    let exclusions = options.exclusions.clone();
+    let walk = WalkBuilder::new(dir)
+        .follow_links(options.follow_symlinks)
+        .hidden(false)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE_NAME)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            let skipped = is_dir && SKIPPED_DIRS.iter().any(|name| entry.file_name() == *name);
+            !skipped && !exclusions.is_excluded(entry.path(), is_dir)
+        })
+        .build();
+
+    let mut paths = Vec::new();
+    let mut warnings = Vec::new();
+    for entry in walk {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => match symlink_loop(&err) {
+                Some((ancestor, child)) => {
+                    warnings.push(format!(
+                        "Skipping symlink loop: {} points to {}",
+                        child.display(),
+                        ancestor.display()
+                    ));
+                    continue;
+                }
+                None => {
+                    return Err(err)
+                        .with_context(|| format!("Failed to read directory: {}", dir.display()))
+                }
+            },
+        };
+        if entry.path_is_symlink() && !options.follow_symlinks {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "elm") {
+            paths.push(path.to_path_buf());
+        }
+    }
+    Ok(FoundFiles::new(paths, warnings))
+}
+
+/// Expand the given files and directories into a sorted list of .elm files,
+/// each only once. Files given explicitly are never excluded.
+pub fn collect_elm_files(inputs: &[PathBuf], options: &WalkOptions) -> Result<FoundFiles> {
+    let mut paths = Vec::new();
+    let mut warnings = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            let found = find_elm_files(input, options)?;
+            paths.extend(found.files);
+            warnings.extend(found.warnings);
+        } else if input.is_file() {
+            paths.push(input.clone());
+        } else {
+            anyhow::bail!("Input path does not exist: {}", input.display());
+        }
+    }
+    Ok(FoundFiles::new(paths, warnings))
+}
+
+/// Compiled formatters shared between files, one per distinct resolved configuration
+#[derive(Debug, Default)]
+pub struct FormatterCache {
+    /// Command-line options applied on top of the config files
+    overrides: ConfigOverrides,
+    /// Resolved configuration per directory
+    configs: HashMap<PathBuf, FormatterConfig>,
+    formatters: HashMap<FormatterConfig, Arc<Formatter>>,
+}
+
+impl FormatterCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A cache whose configurations have the given command-line overrides applied
+    pub fn with_overrides(overrides: ConfigOverrides) -> Self {
+        Self {
+            overrides,
+            ..Self::default()
+        }
+    }
+
+    /// Forget the resolved configurations and compiled formatters, so that
+    /// changed config and query files are read again
+    pub fn clear(&mut self) {
+        self.configs.clear();
+        self.formatters.clear();
+    }
+
+    /// Get the formatter for a configuration, compiling it on first use
+    pub fn get(&mut self, config: &FormatterConfig) -> Result<Arc<Formatter>> {
+        if let Some(formatter) = self.formatters.get(config) {
+            return Ok(Arc::clone(formatter));
+        }
+        let formatter = Arc::new(Formatter::new(config)?);
+        self.formatters
+            .insert(config.clone(), Arc::clone(&formatter));
+        Ok(formatter)
+    }
+
+    /// Get the formatter for a file, using the configuration found for its directory.
+    /// Errors in the configuration are [`ConfigError`](crate::ConfigError)s.
+    pub fn for_file(&mut self, path: &Path) -> Result<Arc<Formatter>> {
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let config = match self.configs.get(&dir) {
+            Some(config) => config.clone(),
+            None => {
+                let config = FormatterConfig::load_with_overrides(Some(&dir), &self.overrides)
+                    .map_err(config_error)?;
+                self.configs.insert(dir, config.clone());
+                config
+            }
+        };
+        self.get(&config).map_err(config_error)
+    }
+}
+
+/// Format a file with the given formatter.
+/// In [`Mode::InPlace`] the file is only written if its content changes.
+pub fn format_file(
+    path: &Path,
+    formatter: &Formatter,
+    mode: Mode,
+    options: &FormatOptions,
+) -> Result<FileReport> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    let formatted = formatter
+        .format_with_options(&content, options)
+        .map_err(|e| in_file(e, path))?;
+    let mut warnings = formatter.warnings(&content)?;
+    if options.tolerate_errors {
+        warnings.extend(formatter.syntax_errors(&content)?);
+    }
+
+    let status = if formatted == content {
+        FileStatus::Unchanged
+    } else {
+        if mode == Mode::InPlace {
+            fs::write(path, &formatted)
+                .with_context(|| format!("Failed to write file: {}", path.display()))?;
+        }
+        FileStatus::Changed {
+            original: content,
+            formatted,
+        }
+    };
+    Ok(FileReport { status, warnings })
+}
+
+/// Default number of parallel jobs: the number of available CPUs
+pub fn default_jobs() -> NonZeroUsize {
+    thread::available_parallelism().unwrap_or(NonZeroUsize::MIN)
+}
+
+/// Format many files concurrently using up to `jobs` threads.
+///
+/// Formatters are resolved up front through `cache`, so each distinct
+/// configuration is still compiled only once. The returned results are in the
+/// same order as `files`, independent of the order in which threads finish.
+pub fn format_files(
+    files: &[PathBuf],
+    cache: &mut FormatterCache,
+    mode: Mode,
+    options: &FormatOptions,
+    jobs: NonZeroUsize,
+) -> Vec<Result<FileReport>> {
+    // Files whose configuration fails to load are reported without formatting
+    let mut pending = Vec::new();
+    let results: Vec<Mutex<Option<Result<FileReport>>>> = files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| match cache.for_file(file) {
+            Ok(formatter) => {
+                pending.push((index, formatter));
+                Mutex::new(None)
+            }
+            Err(e) => Mutex::new(Some(Err(e))),
+        })
+        .collect();
+
+    let next = AtomicUsize::new(0);
+    let workers = jobs.get().min(pending.len());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                while let Some((index, formatter)) =
+                    pending.get(next.fetch_add(1, Ordering::Relaxed))
+                {
+                    let result = format_file(&files[*index], formatter, mode, options);
+                    *results[*index].lock().unwrap() = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|slot| {
+            slot.into_inner()
+                .unwrap()
+                .expect("every file is processed by a worker")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_shares_formatter_per_config() {
+        let mut cache = FormatterCache::new();
+        let config = FormatterConfig::new();
+        let first = cache.get(&config).unwrap();
+        let second = cache.get(&config.clone()).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let other = FormatterConfig {
+            indentation: 4,
+            ..FormatterConfig::new()
+        };
+        assert!(!Arc::ptr_eq(&first, &cache.get(&other).unwrap()));
+    }
+
+    #[test]
+    fn test_format_files_keeps_path_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let formatted = Formatter::new(&FormatterConfig::new())
+            .unwrap()
+            .format("module Main exposing (a)\n\na = 1\n")
+            .unwrap();
+        let mut files = Vec::new();
+        for i in 0..8 {
+            let path = dir.path().join(format!("M{i}.elm"));
+            let content = if i % 2 == 0 {
+                formatted.clone()
+            } else {
+                "module Main exposing (a)\na=1\n".to_string()
+            };
+            fs::write(&path, content).unwrap();
+            files.push(path);
+        }
+
+        let results = format_files(
+            &files,
+            &mut FormatterCache::new(),
+            Mode::Check,
+            &FormatOptions::default(),
+            NonZeroUsize::new(4).unwrap(),
+        );
+        for (i, result) in results.into_iter().enumerate() {
+            let changed = matches!(result.unwrap().status, FileStatus::Changed { .. });
+            assert_eq!(changed, i % 2 == 1, "file {i}");
+        }
+    }
+
+    #[test]
+    fn test_walk_skips_ignored_and_excluded_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in [
+            "src/Main.elm",
+            "src/Generated/Api.elm",
+            "src/Page/Home.elm",
+            "src/Page/Draft.elm",
+            "vendor/Lib.elm",
+            "elm-stuff/0.19.1/Cache.elm",
+        ] {
+            fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            fs::write(root.join(file), "module M exposing (..)\n").unwrap();
+        }
+        fs::write(root.join(".gitignore"), "vendor/\n").unwrap();
+        fs::write(root.join("src/Page").join(IGNORE_FILE_NAME), "Draft.elm\n").unwrap();
+        fs::write(root.join("src/elmfmt.yaml"), "exclude: [Generated/]\n").unwrap();
+
+        let found = find_elm_files(root, &WalkOptions::default()).unwrap().files;
+        assert_eq!(
+            found,
+            vec![root.join("src/Main.elm"), root.join("src/Page/Home.elm")]
+        );
+
+        let exclusions = Exclusions {
+            command_line: Some(Arc::new(
+                build_matcher(root, &["Home.elm".to_string()]).unwrap(),
+            )),
+            ..Exclusions::default()
+        };
+        let options = WalkOptions {
+            exclusions,
+            ..WalkOptions::default()
+        };
+        let found = find_elm_files(root, &options).unwrap().files;
+        assert_eq!(found, vec![root.join("src/Main.elm")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_survives_symlink_loops_and_aliases() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("Page")).unwrap();
+        fs::write(
+            src.join("Page/Home.elm"),
+            "module Page.Home exposing (..)\n",
+        )
+        .unwrap();
+        symlink("..", src.join("Page/loop")).unwrap();
+        symlink("Page/Home.elm", src.join("Alias.elm")).unwrap();
+
+        // The first path in path order is kept
+        let found = find_elm_files(&src, &WalkOptions::default()).unwrap();
+        assert_eq!(found.files, vec![src.join("Alias.elm")]);
+        assert_eq!(found.warnings.len(), 1);
+        assert!(found.warnings[0].contains("symlink loop"));
+
+        let options = WalkOptions {
+            follow_symlinks: false,
+            ..WalkOptions::default()
+        };
+        let found = find_elm_files(&src, &options).unwrap();
+        assert_eq!(found.files, vec![src.join("Page/Home.elm")]);
+        assert!(found.warnings.is_empty());
+    }
+}
@@ -0,0 +1,290 @@
+//! Sorting and de-duplication of import clauses (`sort-imports`)
+
+use anyhow::Result;
+use std::borrow::Cow;
+use std::ops::Range;
+use tree_sitter::Node;
+
+use crate::directives::{directive, Directive};
+use crate::exposing::{add_unique, exposed_items};
+use crate::syntax::{has_comment, is_comment, named_children, parse, text};
+
+/// An import clause together with the comments attached to it
+struct Import<'a> {
+    /// Module name, used as sort key
+    name: &'a str,
+    /// Comments on the lines directly above the import
+    leading_comments: Vec<&'a str>,
+    /// Comment on the same line, after the import
+    trailing_comment: Option<&'a str>,
+    node: Node<'a>,
+}
+
+/// Sort the import clauses of a module alphabetically by module name and merge
+/// duplicate imports of the same module.
+///
+/// Comments between imports move with the import below them, and a comment on
+/// the same line as an import stays with that import. Comments before the first
+/// import are left in place. Inputs that do not parse are returned unchanged so
+/// the formatter can report the error.
+///
+/// `-- elmfmt:` directive comments are fences: the imports between two fences
+/// are sorted on their own, and the imports a directive keeps verbatim are not
+/// touched.
+pub(crate) fn sort_imports(content: &str) -> Result<Cow<'_, str>> {
+    let tree = parse(content)?;
+    let root = tree.root_node();
+    if root.has_error() {
+        return Ok(Cow::Borrowed(content));
+    }
+
+    let children: Vec<Node> = named_children(root).collect();
+    let Some(first) = children.iter().position(|n| n.kind() == "import_clause") else {
+        return Ok(Cow::Borrowed(content));
+    };
+    let last = children
+        .iter()
+        .rposition(|n| n.kind() == "import_clause")
+        .unwrap_or(first);
+
+    // Split the imports into runs between fences
+    let mut runs: Vec<&[Node]> = Vec::new();
+    let mut run_start = first;
+    let mut i = first;
+    while i <= last {
+        let node = children[i];
+        let fence = match node.kind() {
+            "line_comment" => directive(text(content, node)),
+            _ => None,
+        };
+        let Some(fence) = fence else {
+            i += 1;
+            continue;
+        };
+        runs.push(&children[run_start..i]);
+        i = match fence {
+            Directive::Off => children[i + 1..]
+                .iter()
+                .position(|n| {
+                    n.kind() == "line_comment"
+                        && directive(text(content, *n)) == Some(Directive::On)
+                })
+                .map_or(children.len(), |on| i + 1 + on + 1),
+            Directive::Skip => children[i + 1..]
+                .iter()
+                .position(|n| !is_comment(n))
+                .map_or(children.len(), |decl| i + 1 + decl + 1),
+            Directive::On => i + 1,
+        };
+        run_start = i;
+    }
+    if run_start <= last {
+        runs.push(&children[run_start..=last]);
+    }
+
+    let mut result = String::with_capacity(content.len());
+    let mut copied_until = 0;
+    for run in runs {
+        if let Some((range, sorted)) = sort_run(content, run) {
+            result.push_str(&content[copied_until..range.start]);
+            result.push_str(&sorted);
+            copied_until = range.end;
+        }
+    }
+    if copied_until == 0 {
+        return Ok(Cow::Borrowed(content));
+    }
+    result.push_str(&content[copied_until..]);
+    Ok(Cow::Owned(result))
+}
+
+/// Sort and merge a run of imports and comments without directives. Returns
+/// the byte range the sorted imports replace, or `None` without imports.
+fn sort_run(content: &str, run: &[Node]) -> Option<(Range<usize>, String)> {
+    let first = run.iter().position(|n| n.kind() == "import_clause")?;
+    let last = run.iter().rposition(|n| n.kind() == "import_clause")?;
+
+    let mut imports: Vec<Import> = Vec::new();
+    let mut pending_comments = Vec::new();
+    let mut region_end = run[last].end_byte();
+    for (i, node) in run.iter().enumerate().skip(first) {
+        if node.kind() == "import_clause" {
+            if i > last {
+                break;
+            }
+            imports.push(Import {
+                name: text(
+                    content,
+                    node.child_by_field_name("moduleName").unwrap_or(*node),
+                ),
+                leading_comments: std::mem::take(&mut pending_comments),
+                trailing_comment: None,
+                node: *node,
+            });
+        } else if is_comment(node) {
+            let previous = imports.last_mut();
+            match previous {
+                Some(import)
+                    if import.trailing_comment.is_none()
+                        && import.node.end_position().row == node.start_position().row =>
+                {
+                    import.trailing_comment = Some(text(content, *node));
+                    region_end = region_end.max(node.end_byte());
+                }
+                _ if i < last => pending_comments.push(text(content, *node)),
+                _ => break,
+            }
+        } else {
+            break;
+        }
+    }
+
+    // Stable sort keeps the original order of imports of the same module
+    imports.sort_by(|a, b| a.name.cmp(b.name));
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < imports.len() {
+        let mut j = i + 1;
+        while j < imports.len() && imports[j].name == imports[i].name {
+            j += 1;
+        }
+        let group = &imports[i..j];
+        match merge(content, group) {
+            Some(merged) => {
+                lines.extend(
+                    group
+                        .iter()
+                        .flat_map(|import| &import.leading_comments)
+                        .map(|c| c.to_string()),
+                );
+                let trailing: Vec<&str> = group.iter().filter_map(|i| i.trailing_comment).collect();
+                if trailing.is_empty() {
+                    lines.push(merged);
+                } else {
+                    lines.push(format!("{} {}", merged, trailing.join(" ")));
+                }
+            }
+            None => {
+                for import in group {
+                    lines.extend(import.leading_comments.iter().map(|c| c.to_string()));
+                    let clause = text(content, import.node);
+                    match import.trailing_comment {
+                        Some(comment) => lines.push(format!("{} {}", clause, comment)),
+                        None => lines.push(clause.to_string()),
+                    }
+                }
+            }
+        }
+        i = j;
+    }
+
+    Some((run[first].start_byte()..region_end, lines.join("\n")))
+}
+
+/// Merge imports of the same module into a single import clause.
+///
+/// Returns `None` if there is nothing to merge, or if the imports cannot be
+/// merged safely: different aliases or comments inside an import clause.
+/// An import without an alias never merges with an aliased one, since the
+/// alias would hide the full module name.
+fn merge(content: &str, group: &[Import]) -> Option<String> {
+    if group.len() < 2 {
+        return None;
+    }
+    if group.iter().any(|import| has_comment(import.node)) {
+        return None;
+    }
+
+    let mut aliases = Vec::with_capacity(group.len());
+    for import in group {
+        aliases.push(match import.node.child_by_field_name("asClause") {
+            Some(as_clause) => Some(text(content, as_clause.child_by_field_name("name")?)),
+            None => None,
+        });
+    }
+    if aliases.iter().any(|alias| *alias != aliases[0]) {
+        return None;
+    }
+    let alias = aliases[0];
+
+    // Exposed items keyed by name; `Type(..)` wins over `Type`
+    let mut expose_all = false;
+    let mut exposed = Vec::new();
+    for import in group {
+        let Some(list) = import.node.child_by_field_name("exposing") else {
+            continue;
+        };
+        match exposed_items(content, list) {
+            Some(items) => {
+                for item in items {
+                    add_unique(&mut exposed, item);
+                }
+            }
+            None => expose_all = true,
+        }
+    }
+
+    let mut merged = format!("import {}", group[0].name);
+    if let Some(alias) = alias {
+        merged.push_str(" as ");
+        merged.push_str(alias);
+    }
+    if expose_all {
+        merged.push_str(" exposing (..)");
+    } else if !exposed.is_empty() {
+        let items: Vec<&str> = exposed.iter().map(|item| item.text).collect();
+        merged.push_str(&format!(" exposing ({})", items.join(", ")));
+    }
+    Some(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sorts_imports_with_their_comments() {
+        let input = "module Main exposing (..)\n\nimport Set\n-- Html things\nimport Html -- views\nimport Dict\n\n\nx = 1\n";
+        assert_eq!(
+            sort_imports(input).unwrap(),
+            "module Main exposing (..)\n\nimport Dict\n-- Html things\nimport Html -- views\nimport Set\n\n\nx = 1\n"
+        );
+    }
+
+    #[test]
+    fn test_merges_duplicate_imports() {
+        let input = "module Main exposing (..)\n\nimport Html exposing (div, Attribute)\nimport Html exposing (text, div, Html(..))\nimport Html exposing (Html)\n";
+        assert_eq!(
+            sort_imports(input).unwrap(),
+            "module Main exposing (..)\n\nimport Html exposing (div, Attribute, text, Html(..))\n"
+        );
+        let input = "module Main exposing (..)\n\nimport Html as H exposing (div)\nimport Html as H exposing (text)\n";
+        assert_eq!(
+            sort_imports(input).unwrap(),
+            "module Main exposing (..)\n\nimport Html as H exposing (div, text)\n"
+        );
+    }
+
+    #[test]
+    fn test_keeps_aliased_and_plain_imports_apart() {
+        // Merging would hide `Html.text` behind the alias
+        let input = "module Main exposing (..)\n\nimport Html exposing (div)\nimport Html as H\n";
+        assert_eq!(sort_imports(input).unwrap(), input);
+    }
+
+    #[test]
+    fn test_directives_fence_the_imports() {
+        let input = "module Main exposing (..)\n\nimport Set\nimport Dict\n-- elmfmt: off\nimport Task\nimport Array\n-- elmfmt: on\nimport Maybe\nimport Html\n-- elmfmt: skip\nimport Url\nimport Char\nimport Bytes\n\n\nx = 1\n";
+        assert_eq!(
+            sort_imports(input).unwrap(),
+            "module Main exposing (..)\n\nimport Dict\nimport Set\n-- elmfmt: off\nimport Task\nimport Array\n-- elmfmt: on\nimport Html\nimport Maybe\n-- elmfmt: skip\nimport Url\nimport Bytes\nimport Char\n\n\nx = 1\n"
+        );
+    }
+
+    #[test]
+    fn test_keeps_imports_with_conflicting_aliases() {
+        let input = "module Main exposing (..)\n\nimport Html as A\nimport Html as B\n";
+        assert_eq!(sort_imports(input).unwrap(), input);
+    }
+}
@@ -1,8 +1,45 @@
-use anyhow::{anyhow, Result};
-use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fmt;
+use std::fs;
+use std::path::Path;
 use topiary_core::{formatter, Language, Operation, TopiaryQuery};
+use tree_sitter::QueryErrorKind;
 use tree_sitter_language::LanguageFn;
 
+pub mod cli;
+mod config;
+mod diagnostics;
+mod diff;
+mod directives;
+mod docs;
+mod exposing;
+mod files;
+mod imports;
+pub mod lsp;
+mod project;
+mod range;
+mod report;
+mod syntax;
+mod tolerate;
+
+pub use config::{
+    ConfigError, ConfigOverrides, ConfigSource, FormatterConfig, IfStyle, Preset, ResolvedConfig,
+    TupleStyle, CONFIG_FILE_NAME, CONFIG_FILE_NAMES,
+};
+pub use diagnostics::{in_file, IdempotenceError, SyntaxError};
+pub use diff::{changed_lines, colorize_diff, unified_diff};
+pub use files::{
+    collect_elm_files, default_jobs, find_elm_files, format_file, format_files, Exclusions,
+    FileReport, FileStatus, FormatterCache, FoundFiles, Mode, WalkOptions, IGNORE_FILE_NAME,
+};
+pub use project::{find_elm_json, project_source_dirs, ELM_JSON_FILE_NAME};
+pub use range::{RangeUnit, TextRange};
+pub use report::{
+    sarif, write_github, ErrorMessage, FileMessage, LineRange, MessageFormat, Reporter, Status,
+};
+
 extern "C" {
     fn tree_sitter_elm() -> *const ();
 }
@@ -25,69 +62,38 @@ const TUPLE_SPACED_QUERY: &str = include_str!("../queries/tuple_spaced.scm");
 /// The compact tuple style query
 const TUPLE_COMPACT_QUERY: &str = include_str!("../queries/tuple_compact.scm");
 
-/// Default number of spaces for indentation
-const DEFAULT_INDENT_SPACES: u8 = 2;
-
-/// Default number of newlines between top-level declarations
-const DEFAULT_NEWLINES_BETWEEN_DECLS: u8 = 2;
-
-/// Style for if-then-else expressions
-#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
-#[serde(rename_all = "lowercase")]
-pub enum IfStyle {
-    #[default]
-    Indented,
-    Hanging,
-}
+/// Rules of the elmfmt and compact presets
+const ELMFMT_QUERY: &str = include_str!("../queries/elmfmt.scm");
 
-/// Style for tuple expressions
-#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
-#[serde(rename_all = "lowercase")]
-pub enum TupleStyle {
-    Compact,
-    #[default]
-    Spaced,
-}
+/// Rules of the elm-format preset
+const ELM_FORMAT_QUERY: &str = include_str!("../queries/elm_format.scm");
 
-/// Configuration for the formatter
-#[derive(Debug, Deserialize, Serialize, Default, Clone)]
-pub struct FormatterConfig {
-    pub indentation: u8,
-    pub if_style: IfStyle,
-    pub tuple_style: TupleStyle,
-    pub newlines_between_decls: u8,
+/// Options that affect how formatting is run, independent of the style configuration
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    /// Skip the check that formatting the output again does not change it
+    pub skip_idempotence: bool,
+    /// Format the top-level declarations of a module with syntax errors that
+    /// parse, and keep the parts with errors as written
+    pub tolerate_errors: bool,
 }
 
-impl FormatterConfig {
-    pub fn new() -> Self {
-        Self {
-            indentation: DEFAULT_INDENT_SPACES,
-            if_style: IfStyle::default(),
-            tuple_style: TupleStyle::default(),
-            newlines_between_decls: DEFAULT_NEWLINES_BETWEEN_DECLS,
-        }
-    }
+/// A non-fatal problem found in the input, with a 1-based position
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Warning {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
 }
 
-impl FormatterConfig {
-    /// Get the indentation string based on configuration
-    fn indent_string(&self) -> String {
-        " ".repeat(self.indentation as usize)
-    }
-
-    /// Get the delimiter string for newlines between declarations (escaped for query syntax)
-    fn decl_delimiter(&self) -> String {
-        "\\n".repeat((self.newlines_between_decls + 1) as usize)
-    }
-
-    /// Get the delimiter string for section comments (one less newline than decl_delimiter,
-    /// since line_comment already has @append_hardline adding one newline)
-    fn section_comment_delimiter(&self) -> String {
-        "\\n".repeat(self.newlines_between_decls as usize)
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
     }
 }
 
-fn build_query(config: &FormatterConfig) -> String {
+/// Assemble the full Topiary query for the given configuration
+fn build_query(config: &FormatterConfig) -> Result<String> {
     let if_query = match config.if_style {
         IfStyle::Hanging => IF_HANGING_QUERY,
         IfStyle::Indented => IF_INDENTED_QUERY,
@@ -96,42 +102,320 @@ fn build_query(config: &FormatterConfig) -> String {
         TupleStyle::Spaced => TUPLE_SPACED_QUERY,
         TupleStyle::Compact => TUPLE_COMPACT_QUERY,
     };
-    let base_query = format!("{}\n\n{}\n\n{}", ELM_QUERY_BASE, if_query, tuple_query);
+    // Preset rules come first: atoms added to the same node are emitted in query order
+    let preset_query = match config.preset {
+        Preset::Elmfmt | Preset::Compact => ELMFMT_QUERY,
+        Preset::ElmFormat => ELM_FORMAT_QUERY,
+    };
+    let mut base_query = format!(
+        "{}\n\n{}\n\n{}\n\n{}",
+        preset_query, ELM_QUERY_BASE, if_query, tuple_query
+    );
+
+    // User queries come last, so they can add to the rules of the built-in ones
+    for path in &config.extra_queries {
+        let query = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read query file: {}", path.display()))?;
+        validate_query(path, &query)?;
+        base_query.push_str("\n\n");
+        base_query.push_str(&query);
+    }
 
     // Replace the placeholder with the configured delimiter for declaration spacing
     let decl_delimiter = config.decl_delimiter();
     let section_comment_delimiter = config.section_comment_delimiter();
-    base_query
+    Ok(base_query
         .replace("__DECL_DELIMITER__", &decl_delimiter)
-        .replace("__SECTION_COMMENT_DELIMITER__", &section_comment_delimiter)
+        .replace("__SECTION_COMMENT_DELIMITER__", &section_comment_delimiter))
 }
 
-/// Format Elm code with the given configuration
-pub fn format_elm(content: &str, config: &FormatterConfig) -> Result<String> {
-    let grammar = LANGUAGE;
-    let query_str = build_query(config);
-    let query = TopiaryQuery::new(&grammar.into(), &query_str)
-        .map_err(|e| anyhow!("Failed to parse Elm formatting query: {:?}", e))?;
-
-    let language = Language {
-        name: "elm".to_string(),
-        query,
-        grammar: grammar.into(),
-        indent: Some(config.indent_string()),
-    };
+/// The capture names Topiary supports. It only rejects other names while
+/// formatting, so user queries are checked against this list up front.
+const CAPTURE_NAMES: &[&str] = &[
+    "allow_blank_line_before",
+    "append_antispace",
+    "append_begin_measuring_scope",
+    "append_begin_scope",
+    "append_delimiter",
+    "append_empty_scoped_softline",
+    "append_empty_softline",
+    "append_end_measuring_scope",
+    "append_end_scope",
+    "append_hardline",
+    "append_indent_end",
+    "append_indent_start",
+    "append_input_softline",
+    "append_space",
+    "append_spaced_scoped_softline",
+    "append_spaced_softline",
+    "delete",
+    "do_nothing",
+    "leaf",
+    "lower_case",
+    "multi_line_indent_all",
+    "prepend_antispace",
+    "prepend_begin_measuring_scope",
+    "prepend_begin_scope",
+    "prepend_delimiter",
+    "prepend_empty_scoped_softline",
+    "prepend_empty_softline",
+    "prepend_end_measuring_scope",
+    "prepend_end_scope",
+    "prepend_hardline",
+    "prepend_indent_end",
+    "prepend_indent_start",
+    "prepend_input_softline",
+    "prepend_space",
+    "prepend_spaced_scoped_softline",
+    "prepend_spaced_softline",
+    "single_line_no_indent",
+    "upper_case",
+];
+
+/// The captures of a query with their 1-based line and column, skipping
+/// comments and strings
+fn query_captures(query: &str) -> Vec<(&str, usize, usize)> {
+    let mut captures = Vec::new();
+    for (row, line) in query.lines().enumerate() {
+        let mut in_string = false;
+        let mut chars = line.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            match c {
+                '\\' if in_string => {
+                    chars.next();
+                }
+                '"' => in_string = !in_string,
+                ';' if !in_string => break,
+                '@' if !in_string => {
+                    let name = &line[start + 1..];
+                    let end = name
+                        .find(|c: char| !(c.is_alphanumeric() || "_.-".contains(c)))
+                        .unwrap_or(name.len());
+                    captures.push((&name[..end], row + 1, start + 1));
+                }
+                _ => {}
+            }
+        }
+    }
+    captures
+}
 
-    let operation = Operation::Format {
-        skip_idempotence: false,
-        tolerate_parsing_errors: false,
+/// Check a user-supplied query file on its own, so that errors point into it
+fn validate_query(path: &Path, query: &str) -> Result<()> {
+    if TopiaryQuery::new(&LANGUAGE.into(), query).is_ok() {
+        if let Some((name, line, column)) = query_captures(query)
+            .into_iter()
+            .find(|(name, _, _)| !CAPTURE_NAMES.contains(name))
+        {
+            bail!(
+                "{}:{}:{}: invalid capture name: @{} is not a capture Topiary supports",
+                path.display(),
+                line,
+                column,
+                name
+            );
+        }
+        return Ok(());
+    }
+    // Topiary's error does not expose the position, tree-sitter's does
+    let Err(error) = tree_sitter::Query::new(&LANGUAGE.into(), query) else {
+        bail!("Invalid query file: {}", path.display());
+    };
+    let problem = match error.kind {
+        QueryErrorKind::NodeType => "invalid node type",
+        QueryErrorKind::Field => "invalid field name",
+        QueryErrorKind::Capture => "invalid capture name",
+        QueryErrorKind::Predicate => "invalid predicate",
+        QueryErrorKind::Structure => "impossible pattern",
+        QueryErrorKind::Syntax | QueryErrorKind::Language => "invalid syntax",
+    };
+    // Syntax errors come with the offending line and a caret below it
+    let separator = if error.message.contains('\n') {
+        "\n"
+    } else {
+        ": "
     };
+    bail!(
+        "{}:{}:{}: {}{}{}",
+        path.display(),
+        error.row + 1,
+        error.column + 1,
+        problem,
+        separator,
+        error.message.trim_end()
+    )
+}
+
+/// A compiled formatter for one configuration.
+///
+/// Building the Topiary query is the expensive part of formatting, so create a
+/// `Formatter` once and reuse it for every file that shares the configuration.
+#[derive(Debug)]
+pub struct Formatter {
+    config: FormatterConfig,
+    language: Language,
+}
+
+impl Formatter {
+    /// Compile the formatting query for the given configuration.
+    /// Out-of-range values are rejected with a [`ConfigError`].
+    pub fn new(config: &FormatterConfig) -> Result<Self> {
+        config.validate()?;
+        let grammar = LANGUAGE;
+        let query_str = build_query(config)?;
+        let query = TopiaryQuery::new(&grammar.into(), &query_str)
+            .map_err(|e| anyhow!("Failed to parse Elm formatting query: {:?}", e))?;
+
+        let language = Language {
+            name: "elm".to_string(),
+            query,
+            grammar: grammar.into(),
+            indent: Some(config.indent_string()),
+        };
 
-    let mut input = content.as_bytes();
-    let mut output = Vec::new();
+        Ok(Self {
+            config: config.clone(),
+            language,
+        })
+    }
+
+    /// The configuration this formatter was compiled from
+    pub fn config(&self) -> &FormatterConfig {
+        &self.config
+    }
+
+    /// Format Elm code
+    pub fn format(&self, content: &str) -> Result<String> {
+        self.format_with_options(content, &FormatOptions::default())
+    }
 
-    formatter(&mut input, &mut output, &language, operation)
-        .map_err(|e| anyhow!("Failed to format Elm code: {:?}", e))?;
+    /// Format Elm code with the given options
+    pub fn format_with_options(&self, content: &str, options: &FormatOptions) -> Result<String> {
+        if options.tolerate_errors {
+            return tolerate::format_around_errors(content, |content| {
+                self.format_module(content, options)
+            });
+        }
+        self.format_module(content, options)
+    }
 
-    String::from_utf8(output).map_err(|e| anyhow!("Formatter produced invalid UTF-8: {}", e))
+    /// Format a module without syntax errors
+    fn format_module(&self, content: &str, options: &FormatOptions) -> Result<String> {
+        if let Some(error) = SyntaxError::find(content)? {
+            return Err(error.into());
+        }
+        let rewritten = self.rewrite(content)?;
+        let output = self.format_pass(&rewritten)?;
+        if !options.skip_idempotence {
+            // Topiary's own check only reports that the passes differ, so
+            // compare them here to keep both for the error
+            let second = self.format_pass(&output).ok();
+            if second.as_deref() != Some(output.as_str()) {
+                return Err(IdempotenceError {
+                    first: output,
+                    second,
+                }
+                .into());
+            }
+        }
+        Ok(directives::restore_verbatim(content, &output)?.into_owned())
+    }
+
+    /// One formatting pass: the Topiary query, then the layouts it cannot express
+    fn format_pass(&self, content: &str) -> Result<String> {
+        let output = self.run_topiary(content)?;
+        if self.config.exposing_follows_docs {
+            let indent = self.config.indent_string();
+            return Ok(docs::layout_exposing(&output, &indent)?.into_owned());
+        }
+        Ok(output)
+    }
+
+    /// Format with the Topiary query, without its idempotence check
+    fn run_topiary(&self, content: &str) -> Result<String> {
+        let operation = Operation::Format {
+            skip_idempotence: true,
+            tolerate_parsing_errors: false,
+        };
+        let mut input = content.as_bytes();
+        let mut output = Vec::new();
+
+        formatter(&mut input, &mut output, &self.language, operation)
+            .map_err(|e| anyhow!("Failed to format Elm code: {}", e))?;
+
+        String::from_utf8(output).map_err(|e| anyhow!("Formatter produced invalid UTF-8: {}", e))
+    }
+
+    /// The positions of the syntax errors in the input, which formatting with
+    /// [`FormatOptions::tolerate_errors`] keeps as written
+    pub fn syntax_errors(&self, content: &str) -> Result<Vec<Warning>> {
+        tolerate::syntax_errors(content)
+    }
+
+    /// Non-fatal problems in the input that the enabled options look for
+    pub fn warnings(&self, content: &str) -> Result<Vec<Warning>> {
+        if self.config.exposing_follows_docs {
+            docs::undocumented_exposed(content)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Apply the tree-level rewrites enabled in the configuration before the Topiary pass
+    fn rewrite<'a>(&self, content: &'a str) -> Result<Cow<'a, str>> {
+        let mut content = Cow::Borrowed(content);
+        if self.config.sort_imports {
+            content = Cow::Owned(imports::sort_imports(&content)?.into_owned());
+        }
+        if self.config.sort_exposing {
+            content = Cow::Owned(exposing::sort_exposing(&content)?.into_owned());
+        }
+        Ok(content)
+    }
+
+    /// Format only the top-level declarations (`value_declaration`, `type_declaration`,
+    /// `type_alias_declaration` and `type_annotation`) touching `range`.
+    /// The rest of the input is returned byte-for-byte unchanged.
+    pub fn format_range(&self, content: &str, range: &TextRange) -> Result<String> {
+        self.format_range_with_options(content, range, &FormatOptions::default())
+    }
+
+    /// Format the declarations touching `range` with the given options
+    pub fn format_range_with_options(
+        &self,
+        content: &str,
+        range: &TextRange,
+        options: &FormatOptions,
+    ) -> Result<String> {
+        let byte_range = range.to_byte_range(content)?;
+        // Syntax errors outside the range do not keep it from being formatted
+        tolerate::format_range_around_errors(
+            content,
+            &byte_range,
+            options.tolerate_errors,
+            |content, range| {
+                let formatted = self.format_module(content, options)?;
+                range::splice_declarations(content, &formatted, range)
+            },
+        )
+    }
+}
+
+/// Format Elm code with the given configuration.
+///
+/// This compiles the formatting query on every call; use [`Formatter`] when
+/// formatting more than one input.
+pub fn format_elm(content: &str, config: &FormatterConfig) -> Result<String> {
+    Formatter::new(config)?.format(content)
+}
+
+/// Format Elm code with the given configuration and options
+pub fn format_elm_with_options(
+    content: &str,
+    config: &FormatterConfig,
+    options: &FormatOptions,
+) -> Result<String> {
+    Formatter::new(config)?.format_with_options(content, options)
 }
 
 #[cfg(test)]
@@ -145,4 +429,74 @@ mod tests {
         let result = format_elm(input, &config);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_formatter_is_reusable() {
+        let formatter = Formatter::new(&FormatterConfig::new()).unwrap();
+        let first = formatter
+            .format("module Main exposing (a)\n\na = 1\n")
+            .unwrap();
+        let second = formatter
+            .format("module Main exposing (b)\n\nb = 2\n")
+            .unwrap();
+        assert!(first.contains("a = 1"));
+        assert!(second.contains("b = 2"));
+    }
+
+    #[test]
+    fn test_extra_queries_are_appended() {
+        let dir = tempfile::tempdir().unwrap();
+        let query = dir.path().join("team.scm");
+        fs::write(&query, "(value_declaration\n  (eq) @append_hardline\n)\n").unwrap();
+        let config = FormatterConfig {
+            extra_queries: vec![query],
+            ..FormatterConfig::new()
+        };
+        let formatted = format_elm("module Main exposing (a)\n\na = 1\n", &config).unwrap();
+        assert!(formatted.contains("a =\n  1"), "got:\n{}", formatted);
+    }
+
+    #[test]
+    fn test_extra_query_errors_point_to_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let query = dir.path().join("team.scm");
+        fs::write(&query, "; Pipes\n\n(bin_opp_expr) @append_space\n").unwrap();
+        let config = FormatterConfig {
+            extra_queries: vec![query.clone()],
+            ..FormatterConfig::new()
+        };
+        let error = Formatter::new(&config).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            format!("{}:3:2: invalid node type: bin_opp_expr", query.display())
+        );
+    }
+
+    #[test]
+    fn test_extra_query_capture_names_are_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let query = dir.path().join("team.scm");
+        let config = FormatterConfig {
+            extra_queries: vec![query.clone()],
+            ..FormatterConfig::new()
+        };
+
+        // Names in comments and strings are not captures
+        fs::write(
+            &query,
+            "; @not_a_capture\n((eq) @append_delimiter (#delimiter! \"@x\"))\n",
+        )
+        .unwrap();
+        assert!(Formatter::new(&config).is_ok());
+
+        fs::write(&query, "; Typo\n(value_declaration (eq) @bogus_capture)\n").unwrap();
+        let error = Formatter::new(&config).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            format!(
+                "{}:2:25: invalid capture name: @bogus_capture is not a capture Topiary supports",
+                query.display()
+            )
+        );
+    }
 }
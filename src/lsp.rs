@@ -0,0 +1,495 @@
+use anyhow::{anyhow, Result};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidChangeWatchedFiles, DidCloseTextDocument, DidOpenTextDocument,
+    DidSaveTextDocument, Notification as NotificationTrait,
+};
+use lsp_types::request::{
+    Formatting, OnTypeFormatting, RangeFormatting, RegisterCapability, Request as RequestTrait,
+};
+use lsp_types::{
+    DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
+    DidChangeWatchedFilesRegistrationOptions, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DidSaveTextDocumentParams, DocumentFormattingParams,
+    DocumentOnTypeFormattingOptions, DocumentOnTypeFormattingParams, DocumentRangeFormattingParams,
+    FileSystemWatcher, GlobPattern, InitializeParams, OneOf, Position, Range, Registration,
+    RegistrationParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextDocumentSyncSaveOptions, TextEdit, Url,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{
+    FormatOptions, Formatter, FormatterCache, FormatterConfig, TextRange, CONFIG_FILE_NAMES,
+    ELM_JSON_FILE_NAME,
+};
+
+/// Whether a URI points to a file the configuration is read from
+fn is_config_file(uri: &Url) -> bool {
+    uri.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .is_some_and(|name| name == ELM_JSON_FILE_NAME || CONFIG_FILE_NAMES.contains(&name))
+}
+
+/// Run the language server over stdin/stdout until the client exits
+pub fn run_stdio() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    serve(&connection)?;
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Run the language server on an established connection
+pub fn serve(connection: &Connection) -> Result<()> {
+    let capabilities = ServerCapabilities {
+        // Saves are reported so that saving a config file reloads it
+        text_document_sync: Some(TextDocumentSyncCapability::Options(
+            TextDocumentSyncOptions {
+                open_close: Some(true),
+                change: Some(TextDocumentSyncKind::FULL),
+                save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                ..TextDocumentSyncOptions::default()
+            },
+        )),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+            first_trigger_character: "\n".to_string(),
+            more_trigger_character: None,
+        }),
+        ..ServerCapabilities::default()
+    };
+    let params: InitializeParams =
+        serde_json::from_value(connection.initialize(serde_json::to_value(capabilities)?)?)?;
+
+    // Ask the client to report changes of config files made outside the editor
+    let can_watch = params
+        .capabilities
+        .workspace
+        .and_then(|workspace| workspace.did_change_watched_files)
+        .and_then(|capability| capability.dynamic_registration)
+        .unwrap_or(false);
+    if can_watch {
+        let watchers = CONFIG_FILE_NAMES
+            .iter()
+            .chain([&ELM_JSON_FILE_NAME])
+            .map(|name| FileSystemWatcher {
+                glob_pattern: GlobPattern::String(format!("**/{name}")),
+                kind: None,
+            })
+            .collect();
+        let registration = Registration {
+            id: "elmfmt-config-files".to_string(),
+            method: DidChangeWatchedFiles::METHOD.to_string(),
+            register_options: Some(serde_json::to_value(
+                DidChangeWatchedFilesRegistrationOptions { watchers },
+            )?),
+        };
+        let params = RegistrationParams {
+            registrations: vec![registration],
+        };
+        connection.sender.send(Message::Request(Request::new(
+            RequestId::from("register-config-watchers".to_string()),
+            RegisterCapability::METHOD.to_string(),
+            params,
+        )))?;
+    }
+
+    let mut server = Server::default();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let response = server.handle_request(request);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                // A notification cannot be answered, so a bad one is only logged
+                if let Err(e) = server.handle_notification(notification) {
+                    eprintln!("elmfmt: ignoring notification: {:#}", e);
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Open documents and compiled formatters of a running server. Configurations
+/// are cached until a config file is saved or reported as changed.
+#[derive(Default)]
+struct Server {
+    documents: HashMap<Url, String>,
+    formatters: FormatterCache,
+}
+
+impl Server {
+    fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    notification.extract(DidOpenTextDocument::METHOD)?;
+                self.documents
+                    .insert(params.text_document.uri, params.text_document.text);
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    notification.extract(DidChangeTextDocument::METHOD)?;
+                // Full document sync: the last change holds the whole text
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents.insert(params.text_document.uri, change.text);
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    notification.extract(DidCloseTextDocument::METHOD)?;
+                self.documents.remove(&params.text_document.uri);
+            }
+            DidSaveTextDocument::METHOD => {
+                let params: DidSaveTextDocumentParams =
+                    notification.extract(DidSaveTextDocument::METHOD)?;
+                if is_config_file(&params.text_document.uri) {
+                    self.formatters.clear();
+                }
+            }
+            DidChangeWatchedFiles::METHOD => {
+                let params: DidChangeWatchedFilesParams =
+                    notification.extract(DidChangeWatchedFiles::METHOD)?;
+                if params
+                    .changes
+                    .iter()
+                    .any(|change| is_config_file(&change.uri))
+                {
+                    self.formatters.clear();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            Formatting::METHOD => self.formatting(request),
+            RangeFormatting::METHOD => self.range_formatting(request),
+            OnTypeFormatting::METHOD => self.on_type_formatting(request),
+            _ => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("Unsupported request: {}", request.method),
+                )
+            }
+        };
+        match result {
+            Ok(edits) => Response::new_ok(id, edits),
+            Err(e) => Response::new_err(id, ErrorCode::RequestFailed as i32, format!("{:#}", e)),
+        }
+    }
+
+    fn formatting(&mut self, request: Request) -> Result<Option<Vec<TextEdit>>> {
+        let (_, params): (RequestId, DocumentFormattingParams) =
+            request.extract(Formatting::METHOD)?;
+        let uri = params.text_document.uri;
+        let text = self.document(&uri)?;
+        let formatted = self
+            .formatter(&uri)?
+            .format_with_options(&text, &tolerant_options())?;
+        Ok(Some(text_edits(&text, &formatted)))
+    }
+
+    fn range_formatting(&mut self, request: Request) -> Result<Option<Vec<TextEdit>>> {
+        let (_, params): (RequestId, DocumentRangeFormattingParams) =
+            request.extract(RangeFormatting::METHOD)?;
+        let uri = params.text_document.uri;
+        let text = self.document(&uri)?;
+        let range = TextRange::Bytes {
+            start: offset_at(&text, params.range.start),
+            end: offset_at(&text, params.range.end),
+        };
+        let formatted =
+            self.formatter(&uri)?
+                .format_range_with_options(&text, &range, &tolerant_options())?;
+        Ok(Some(text_edits(&text, &formatted)))
+    }
+
+    fn on_type_formatting(&mut self, request: Request) -> Result<Option<Vec<TextEdit>>> {
+        let (_, params): (RequestId, DocumentOnTypeFormattingParams) =
+            request.extract(OnTypeFormatting::METHOD)?;
+        let position = params.text_document_position;
+        let uri = position.text_document.uri;
+        let text = self.document(&uri)?;
+
+        // Format the declaration the typed character ended up in
+        let offset = offset_at(&text, position.position);
+        let offset = text[..offset]
+            .strip_suffix(params.ch.as_str())
+            .map_or(offset, str::len);
+        let range = TextRange::Bytes {
+            start: offset,
+            end: offset,
+        };
+
+        // The document is usually incomplete while typing, so a failure is not an error
+        let formatter = self.formatter(&uri)?;
+        match formatter.format_range_with_options(&text, &range, &tolerant_options()) {
+            Ok(formatted) => Ok(Some(text_edits(&text, &formatted))),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn document(&self, uri: &Url) -> Result<String> {
+        self.documents
+            .get(uri)
+            .cloned()
+            .ok_or_else(|| anyhow!("Document is not open: {}", uri))
+    }
+
+    /// The formatter for a document, configured by the elmfmt.yaml found from its directory
+    fn formatter(&mut self, uri: &Url) -> Result<Arc<Formatter>> {
+        match uri.to_file_path() {
+            Ok(path) => self.formatters.for_file(&path),
+            Err(()) => {
+                let config = FormatterConfig::load(None)?;
+                self.formatters.get(&config)
+            }
+        }
+    }
+}
+
+/// The edits turning `original` into `formatted`: a single edit covering the
+/// differing middle part, or none if they are equal.
+fn text_edits(original: &str, formatted: &str) -> Vec<TextEdit> {
+    if original == formatted {
+        return Vec::new();
+    }
+
+    let mut prefix = original
+        .bytes()
+        .zip(formatted.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !original.is_char_boundary(prefix) || !formatted.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+
+    let max_suffix = original.len().min(formatted.len()) - prefix;
+    let mut suffix = original
+        .bytes()
+        .rev()
+        .zip(formatted.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !original.is_char_boundary(original.len() - suffix)
+        || !formatted.is_char_boundary(formatted.len() - suffix)
+    {
+        suffix -= 1;
+    }
+
+    vec![TextEdit {
+        range: Range {
+            start: position_at(original, prefix),
+            end: position_at(original, original.len() - suffix),
+        },
+        new_text: formatted[prefix..formatted.len() - suffix].to_string(),
+    }]
+}
+
+/// Convert a byte offset into an LSP position (line and UTF-16 column)
+fn position_at(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+/// Options for formatting in the editor, which should still work while the
+/// document has a syntax error
+fn tolerant_options() -> FormatOptions {
+    FormatOptions {
+        tolerate_errors: true,
+        ..FormatOptions::default()
+    }
+}
+
+/// Convert an LSP position into a byte offset, clamping positions past the
+/// end of a line or of the document
+fn offset_at(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let line_end = text[line_start..]
+        .find('\n')
+        .map_or(text.len(), |i| line_start + i);
+
+    let mut units = 0;
+    for (i, c) in text[line_start..line_end].char_indices() {
+        if units >= position.character as usize {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    line_end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{
+        FormattingOptions, InitializeParams, TextDocumentIdentifier, TextDocumentItem,
+    };
+
+    #[test]
+    fn test_position_conversion_uses_utf16_columns() {
+        let text = "a = \"é𝄞\"\nb = 2\n";
+        let offset = text.find("\"\n").unwrap();
+        let position = position_at(text, offset);
+        assert_eq!(position, Position::new(0, 8));
+        assert_eq!(offset_at(text, position), offset);
+        assert_eq!(offset_at(text, Position::new(1, 99)), text.len() - 1);
+    }
+
+    #[test]
+    fn test_text_edits_cover_only_the_change() {
+        let edits = text_edits("a=1\nb = 2\n", "a = 1\nb = 2\n");
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range.start, Position::new(0, 1));
+        assert_eq!(edits[0].range.end, Position::new(0, 2));
+        assert_eq!(edits[0].new_text, " = ");
+        assert!(text_edits("a\n", "a\n").is_empty());
+    }
+
+    #[test]
+    fn test_config_changes_are_picked_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("elmfmt.yaml");
+        std::fs::write(&config, "indentation: 2\n").unwrap();
+        let uri = Url::from_file_path(dir.path().join("Main.elm")).unwrap();
+
+        let mut server = Server::default();
+        let notify = |server: &mut Server, method: &str, params: serde_json::Value| {
+            let notification = Notification::new(method.to_string(), params);
+            server.handle_notification(notification).unwrap();
+        };
+        let text = "module Main exposing (a)\n\n\na =\n 1\n";
+        let format = |server: &mut Server| {
+            let params = DocumentFormattingParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                options: FormattingOptions::default(),
+                work_done_progress_params: Default::default(),
+            };
+            let request = Request::new(1.into(), Formatting::METHOD.to_string(), params);
+            let response = server.handle_request(request);
+            let edits: Vec<TextEdit> = serde_json::from_value(response.result.unwrap()).unwrap();
+            let start = offset_at(text, edits[0].range.start);
+            let end = offset_at(text, edits[0].range.end);
+            format!("{}{}{}", &text[..start], edits[0].new_text, &text[end..])
+        };
+        notify(
+            &mut server,
+            DidOpenTextDocument::METHOD,
+            serde_json::json!({
+                "textDocument": {
+                    "uri": uri, "languageId": "elm", "version": 1,
+                    "text": text,
+                }
+            }),
+        );
+        assert_eq!(
+            format(&mut server),
+            "module Main exposing (a)\n\na =\n  1\n"
+        );
+
+        std::fs::write(&config, "indentation: 4\n").unwrap();
+        let config_uri = Url::from_file_path(&config).unwrap();
+        notify(
+            &mut server,
+            DidChangeWatchedFiles::METHOD,
+            serde_json::json!({ "changes": [{ "uri": config_uri, "type": 2 }] }),
+        );
+        assert_eq!(
+            format(&mut server),
+            "module Main exposing (a)\n\na =\n    1\n"
+        );
+    }
+
+    #[test]
+    fn test_formatting_request() {
+        let (server, client) = Connection::memory();
+        let server_thread = std::thread::spawn(move || serve(&server));
+
+        let request = |id: i32, method: &str, params: serde_json::Value| {
+            client
+                .sender
+                .send(Message::Request(Request::new(
+                    id.into(),
+                    method.to_string(),
+                    params,
+                )))
+                .unwrap();
+            match client.receiver.recv().unwrap() {
+                Message::Response(response) => response,
+                other => panic!("Expected a response, got {:?}", other),
+            }
+        };
+        let notify = |method: &str, params: serde_json::Value| {
+            client
+                .sender
+                .send(Message::Notification(Notification::new(
+                    method.to_string(),
+                    params,
+                )))
+                .unwrap();
+        };
+
+        let initialize = serde_json::to_value(InitializeParams::default()).unwrap();
+        assert!(request(1, "initialize", initialize).error.is_none());
+        notify("initialized", serde_json::json!({}));
+
+        let uri = Url::parse("untitled:Main.elm").unwrap();
+        notify(
+            DidOpenTextDocument::METHOD,
+            serde_json::to_value(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "elm".to_string(),
+                    1,
+                    "module Main exposing (a)\n\na=1\n".to_string(),
+                ),
+            })
+            .unwrap(),
+        );
+
+        // Bad params must not stop the server
+        notify(
+            DidOpenTextDocument::METHOD,
+            serde_json::json!({ "textDocument": 1 }),
+        );
+
+        let params = DocumentFormattingParams {
+            text_document: TextDocumentIdentifier::new(uri),
+            options: FormattingOptions::default(),
+            work_done_progress_params: Default::default(),
+        };
+        let response = request(2, Formatting::METHOD, serde_json::to_value(params).unwrap());
+        let edits: Vec<TextEdit> = serde_json::from_value(response.result.unwrap()).unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, " = ");
+
+        assert!(request(3, "shutdown", serde_json::Value::Null)
+            .error
+            .is_none());
+        notify("exit", serde_json::Value::Null);
+        server_thread.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,118 @@
+//! Elm projects: settings and source directories from `elm.json`
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_yaml::Mapping;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{located_error, search_start, ConfigKeys};
+
+/// Elm project file name
+pub const ELM_JSON_FILE_NAME: &str = "elm.json";
+
+/// The parts of `elm.json` that elmfmt uses
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ElmJson {
+    #[serde(rename = "type")]
+    project_type: ProjectType,
+    /// Source directories of an application
+    #[serde(default)]
+    source_directories: Vec<PathBuf>,
+    /// Formatter settings, with the same keys as `elmfmt.yaml`
+    elmfmt: Option<ConfigKeys>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ProjectType {
+    Application,
+    Package,
+}
+
+impl ElmJson {
+    fn read(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content)
+            .map_err(|e| located_error(path, &e.to_string(), e.line(), e.column()))
+    }
+}
+
+/// The formatter settings in the `"elmfmt"` object of an `elm.json` file, if any
+pub(crate) fn config_keys(elm_json: &Path) -> Result<Option<Mapping>> {
+    Ok(ElmJson::read(elm_json)?.elmfmt.map(|keys| keys.0))
+}
+
+/// Find the `elm.json` of the project containing the given directory (or the
+/// current directory)
+pub fn find_elm_json(start_dir: Option<&Path>) -> Option<PathBuf> {
+    search_start(start_dir)
+        .ancestors()
+        .map(|dir| dir.join(ELM_JSON_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// The source directories of the Elm project containing the given directory (or
+/// the current directory): the `source-directories` of an application, or `src`
+/// for a package. `None` if there is no `elm.json`.
+pub fn project_source_dirs(start_dir: Option<&Path>) -> Result<Option<Vec<PathBuf>>> {
+    let Some(elm_json) = find_elm_json(start_dir) else {
+        return Ok(None);
+    };
+    let root = elm_json.parent().unwrap_or(Path::new("."));
+    let project = ElmJson::read(&elm_json)?;
+    let dirs = match project.project_type {
+        ProjectType::Application => project.source_directories,
+        ProjectType::Package => vec![PathBuf::from("src")],
+    };
+    Ok(Some(dirs.iter().map(|dir| root.join(dir)).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_application_source_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(ELM_JSON_FILE_NAME),
+            r#"{ "type": "application", "source-directories": ["src", "../shared"], "elmfmt": { "indentation": 4 } }"#,
+        )
+        .unwrap();
+        let nested = dir.path().join("src/Page");
+        fs::create_dir_all(&nested).unwrap();
+
+        assert_eq!(
+            project_source_dirs(Some(&nested)).unwrap(),
+            Some(vec![dir.path().join("src"), dir.path().join("../shared")])
+        );
+        let keys = config_keys(&dir.path().join(ELM_JSON_FILE_NAME))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            keys.get("indentation").and_then(serde_yaml::Value::as_u64),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn test_package_sources_are_in_src() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(ELM_JSON_FILE_NAME),
+            r#"{ "type": "package", "name": "author/project" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            project_source_dirs(Some(dir.path())).unwrap(),
+            Some(vec![dir.path().join("src")])
+        );
+        assert_eq!(
+            config_keys(&dir.path().join(ELM_JSON_FILE_NAME)).unwrap(),
+            None
+        );
+    }
+}
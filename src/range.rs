@@ -0,0 +1,170 @@
+use anyhow::{bail, Context, Result};
+use std::ops::Range;
+use std::str::FromStr;
+use tree_sitter::Node;
+
+use crate::syntax::{named_children, parse};
+
+/// Top-level node kinds that are formatted as a unit by range formatting
+const DECLARATION_KINDS: &[&str] = &[
+    "value_declaration",
+    "type_declaration",
+    "type_alias_declaration",
+    "type_annotation",
+];
+
+/// A region of the input selected for range formatting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextRange {
+    /// 1-based, inclusive line numbers
+    Lines { start: usize, end: usize },
+    /// 0-based byte offsets, end exclusive
+    Bytes { start: usize, end: usize },
+}
+
+/// Unit of the `start:end` pair given to `--range`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum RangeUnit {
+    /// 1-based, inclusive line numbers
+    #[default]
+    Lines,
+    /// 0-based byte offsets, end exclusive
+    Bytes,
+}
+
+impl TextRange {
+    /// Parse a `start:end` pair in the given unit
+    pub fn parse(s: &str, unit: RangeUnit) -> Result<Self> {
+        let (start, end) = s
+            .split_once(':')
+            .with_context(|| format!("Invalid range '{}', expected START:END", s))?;
+        let start = usize::from_str(start.trim())
+            .with_context(|| format!("Invalid range start '{}'", start))?;
+        let end =
+            usize::from_str(end.trim()).with_context(|| format!("Invalid range end '{}'", end))?;
+        if end < start {
+            bail!("Invalid range '{}': end is before start", s);
+        }
+        match unit {
+            RangeUnit::Lines if start == 0 => bail!("Line numbers start at 1"),
+            RangeUnit::Lines => Ok(TextRange::Lines { start, end }),
+            RangeUnit::Bytes => Ok(TextRange::Bytes { start, end }),
+        }
+    }
+
+    /// Resolve the range to byte offsets within `content`
+    pub fn to_byte_range(&self, content: &str) -> Result<Range<usize>> {
+        match *self {
+            TextRange::Bytes { start, end } => {
+                if end > content.len() {
+                    bail!(
+                        "Byte range {}:{} is outside the input ({} bytes)",
+                        start,
+                        end,
+                        content.len()
+                    );
+                }
+                Ok(start..end)
+            }
+            TextRange::Lines { start, end } => {
+                let line_starts: Vec<usize> = std::iter::once(0)
+                    .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+                    .collect();
+                let line_count = line_starts.len();
+                if start > line_count {
+                    bail!(
+                        "Line range {}:{} is outside the input ({} lines)",
+                        start,
+                        end,
+                        line_count
+                    );
+                }
+                let byte_start = line_starts[start - 1];
+                let byte_end = line_starts.get(end).copied().unwrap_or(content.len());
+                Ok(byte_start..byte_end)
+            }
+        }
+    }
+}
+
+/// Top-level declarations of a file, in source order
+fn declarations<'tree>(root: Node<'tree>) -> Vec<Node<'tree>> {
+    named_children(root)
+        .filter(|node| DECLARATION_KINDS.contains(&node.kind()))
+        .collect()
+}
+
+/// Whether a node overlaps the byte range. An empty range (a cursor position)
+/// touches the node it is placed in.
+fn touches(node: &Node, range: &Range<usize>) -> bool {
+    if range.is_empty() {
+        node.start_byte() <= range.start && range.start <= node.end_byte()
+    } else {
+        node.start_byte() < range.end && range.start < node.end_byte()
+    }
+}
+
+/// Splice the formatted versions of the declarations touching `range` into
+/// `original`, leaving everything else untouched.
+///
+/// `formatted` must be the whole-file formatting of `original`; declarations are
+/// matched between the two by their position among the top-level declarations.
+pub(crate) fn splice_declarations(
+    original: &str,
+    formatted: &str,
+    range: &Range<usize>,
+) -> Result<String> {
+    let original_tree = parse(original)?;
+    let formatted_tree = parse(formatted)?;
+    let original_decls = declarations(original_tree.root_node());
+    let formatted_decls = declarations(formatted_tree.root_node());
+
+    let same_shape = original_decls.len() == formatted_decls.len()
+        && original_decls
+            .iter()
+            .zip(&formatted_decls)
+            .all(|(a, b)| a.kind() == b.kind());
+    if !same_shape {
+        bail!("Formatting changed the top-level declarations; cannot format a range");
+    }
+
+    let mut result = String::with_capacity(original.len());
+    let mut copied_until = 0;
+    for (decl, formatted_decl) in original_decls.iter().zip(&formatted_decls) {
+        if !touches(decl, range) {
+            continue;
+        }
+        result.push_str(&original[copied_until..decl.start_byte()]);
+        result.push_str(&formatted[formatted_decl.byte_range()]);
+        copied_until = decl.end_byte();
+    }
+    result.push_str(&original[copied_until..]);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_range_to_bytes() {
+        let content = "a\nbb\nccc\n";
+        let range = TextRange::parse("2:3", RangeUnit::Lines).unwrap();
+        assert_eq!(range.to_byte_range(content).unwrap(), 2..9);
+        assert!(TextRange::parse("0:1", RangeUnit::Lines).is_err());
+        assert!(TextRange::parse("3:2", RangeUnit::Bytes).is_err());
+    }
+
+    #[test]
+    fn test_splice_only_touched_declarations() {
+        let original = "module Main exposing (..)\n\n\na=1\n\n\nb=2\n";
+        let formatted = "module Main exposing (..)\n\n\na = 1\n\n\nb = 2\n";
+        let range = TextRange::Lines { start: 7, end: 7 }
+            .to_byte_range(original)
+            .unwrap();
+        assert_eq!(
+            splice_declarations(original, formatted, &range).unwrap(),
+            "module Main exposing (..)\n\n\na=1\n\n\nb = 2\n"
+        );
+    }
+}
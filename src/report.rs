@@ -0,0 +1,451 @@
+//! Machine-readable reports of formatting runs
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::Path;
+
+use crate::diff::changed_lines;
+use crate::{FileReport, FileStatus, Mode, SyntaxError, Warning};
+
+/// How results are reported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MessageFormat {
+    /// Text for people, on stderr
+    #[default]
+    Human,
+    /// One JSON object per file, on stdout
+    Json,
+    /// A SARIF 2.1.0 log, on stdout
+    Sarif,
+    /// GitHub Actions workflow commands that annotate the files, on stdout
+    Github,
+}
+
+/// What happened to a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// The file was rewritten
+    Formatted,
+    /// The file was already formatted
+    Unchanged,
+    /// The file is not formatted (checked only)
+    WouldReformat,
+    /// The file could not be formatted
+    Error,
+}
+
+/// A 1-based, inclusive range of lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Why a file could not be formatted, with the position for syntax errors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorMessage {
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+}
+
+impl ErrorMessage {
+    pub fn new(error: &anyhow::Error) -> Self {
+        match error.downcast_ref::<SyntaxError>() {
+            Some(error) => Self {
+                message: format!("syntax error: {}", error.message),
+                line: Some(error.line),
+                column: Some(error.column),
+            },
+            None => Self {
+                message: format!("{error:#}"),
+                line: None,
+                column: None,
+            },
+        }
+    }
+}
+
+/// The report of one file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileMessage {
+    pub path: String,
+    pub status: Status,
+    /// Lines of the original file that formatting changes
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changed_lines: Vec<LineRange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Warning>,
+}
+
+impl FileMessage {
+    pub fn new(path: &Path, result: &Result<FileReport>, mode: Mode) -> Self {
+        let path = path.display().to_string();
+        let report = match result {
+            Ok(report) => report,
+            Err(error) => {
+                return Self {
+                    path,
+                    status: Status::Error,
+                    changed_lines: Vec::new(),
+                    error: Some(ErrorMessage::new(error)),
+                    warnings: Vec::new(),
+                }
+            }
+        };
+        let (status, changed_lines) = match &report.status {
+            FileStatus::Unchanged => (Status::Unchanged, Vec::new()),
+            FileStatus::Changed {
+                original,
+                formatted,
+            } => {
+                let status = match mode {
+                    Mode::Check => Status::WouldReformat,
+                    Mode::InPlace => Status::Formatted,
+                };
+                let lines = changed_lines(original, formatted)
+                    .into_iter()
+                    .map(|range| LineRange {
+                        start: *range.start(),
+                        end: *range.end(),
+                    })
+                    .collect();
+                (status, lines)
+            }
+        };
+        Self {
+            path,
+            status,
+            changed_lines,
+            error: None,
+            warnings: report.warnings.clone(),
+        }
+    }
+
+    /// Write the report as one line of JSON
+    pub fn write_json(&self, out: &mut impl Write) -> Result<()> {
+        serde_json::to_writer(&mut *out, self)?;
+        writeln!(out)?;
+        Ok(())
+    }
+}
+
+/// Writes the reports of files in a machine-readable format as they are
+/// processed. Formats that describe the whole run are written by
+/// [`Reporter::finish`].
+pub struct Reporter<W: Write> {
+    format: MessageFormat,
+    out: W,
+    messages: Vec<FileMessage>,
+}
+
+impl<W: Write> Reporter<W> {
+    pub fn new(format: MessageFormat, out: W) -> Self {
+        Self {
+            format,
+            out,
+            messages: Vec::new(),
+        }
+    }
+
+    pub fn report(&mut self, message: FileMessage) -> Result<()> {
+        match self.format {
+            MessageFormat::Human => {}
+            MessageFormat::Json => message.write_json(&mut self.out)?,
+            MessageFormat::Github => write_github(&message, &mut self.out)?,
+            MessageFormat::Sarif => self.messages.push(message),
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        if self.format == MessageFormat::Sarif {
+            serde_json::to_writer_pretty(&mut self.out, &sarif(&self.messages))?;
+            writeln!(self.out)?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Escape the message of a workflow command
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a property value of a workflow command
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Write GitHub Actions workflow commands that annotate the changed lines,
+/// the error and the warnings of a file
+pub fn write_github(message: &FileMessage, out: &mut impl Write) -> Result<()> {
+    let file = escape_property(&message.path);
+    let (level, title) = match message.status {
+        Status::Formatted => ("notice", "Formatted by elmfmt"),
+        _ => ("warning", "Not formatted"),
+    };
+    for range in &message.changed_lines {
+        writeln!(
+            out,
+            "::{level} file={file},line={},endLine={},title={}::{}",
+            range.start,
+            range.end,
+            escape_property(title),
+            escape_data("Run elmfmt to format these lines"),
+        )?;
+    }
+    if let Some(error) = &message.error {
+        let mut location = format!("file={file}");
+        if let (Some(line), Some(column)) = (error.line, error.column) {
+            location.push_str(&format!(",line={line},col={column}"));
+        }
+        writeln!(
+            out,
+            "::error {location},title=elmfmt::{}",
+            escape_data(&error.message)
+        )?;
+    }
+    for warning in &message.warnings {
+        writeln!(
+            out,
+            "::warning file={file},line={},col={},title=elmfmt::{}",
+            warning.line,
+            warning.column,
+            escape_data(&warning.message)
+        )?;
+    }
+    Ok(())
+}
+
+/// SARIF rules of the results
+const RULES: &[(&str, &str)] = &[
+    ("unformatted", "The code is not formatted"),
+    ("syntax-error", "The file does not parse"),
+    ("format-error", "The file could not be formatted"),
+    ("warning", "A problem found while formatting"),
+];
+
+/// The SARIF artifact URI of a path: percent-encoded segments, relative
+/// paths without a leading `./`, and absolute paths as `file://` URIs
+fn artifact_uri(path: &str) -> String {
+    let mut path = path.replace('\\', "/");
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest.to_string();
+    }
+    let is_drive = |segment: &str| {
+        segment.len() == 2 && segment.ends_with(':') && segment.as_bytes()[0].is_ascii_alphabetic()
+    };
+    let encoded: Vec<String> = path
+        .split('/')
+        .map(|segment| {
+            if is_drive(segment) {
+                return segment.to_string();
+            }
+            let mut encoded = String::with_capacity(segment.len());
+            for byte in segment.bytes() {
+                if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                    encoded.push(byte as char);
+                } else {
+                    encoded.push_str(&format!("%{byte:02X}"));
+                }
+            }
+            encoded
+        })
+        .collect();
+    let uri = encoded.join("/");
+    if path.starts_with('/') {
+        format!("file://{uri}")
+    } else if path.split('/').next().is_some_and(is_drive) {
+        format!("file:///{uri}")
+    } else {
+        uri
+    }
+}
+
+/// A SARIF result located in a file
+fn sarif_result(rule: &str, level: &str, text: &str, path: &str, region: Value) -> Value {
+    json!({
+        "ruleId": rule,
+        "level": level,
+        "message": { "text": text },
+        "locations": [{
+            "physicalLocation": {
+                "artifactLocation": { "uri": artifact_uri(path) },
+                "region": region,
+            }
+        }]
+    })
+}
+
+/// A SARIF 2.1.0 log of the reports of a run
+pub fn sarif(messages: &[FileMessage]) -> Value {
+    let mut results = Vec::new();
+    for message in messages {
+        let path = &message.path;
+        let (level, text) = match message.status {
+            Status::Formatted => ("note", "Formatted by elmfmt"),
+            _ => ("warning", "Not formatted, run elmfmt to format these lines"),
+        };
+        for range in &message.changed_lines {
+            let region = json!({ "startLine": range.start, "endLine": range.end });
+            results.push(sarif_result("unformatted", level, text, path, region));
+        }
+        if let Some(error) = &message.error {
+            let (rule, region) = match (error.line, error.column) {
+                (Some(line), Some(column)) => (
+                    "syntax-error",
+                    json!({ "startLine": line, "startColumn": column }),
+                ),
+                _ => ("format-error", json!({ "startLine": 1 })),
+            };
+            results.push(sarif_result(rule, "error", &error.message, path, region));
+        }
+        for warning in &message.warnings {
+            let region = json!({ "startLine": warning.line, "startColumn": warning.column });
+            results.push(sarif_result(
+                "warning",
+                "warning",
+                &warning.message,
+                path,
+                region,
+            ));
+        }
+    }
+    let rules: Vec<Value> = RULES
+        .iter()
+        .map(|(id, description)| json!({ "id": id, "shortDescription": { "text": description } }))
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "elmfmt",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_json_messages() {
+        let changed = Ok(FileReport {
+            status: FileStatus::Changed {
+                original: "a = 1\nb=2\n".to_string(),
+                formatted: "a = 1\nb = 2\n".to_string(),
+            },
+            warnings: Vec::new(),
+        });
+        let message = FileMessage::new(Path::new("src/Main.elm"), &changed, Mode::Check);
+        let mut out = Vec::new();
+        message.write_json(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"path\":\"src/Main.elm\",\"status\":\"would_reformat\",\"changed_lines\":[{\"start\":2,\"end\":2}]}\n"
+        );
+
+        let error = SyntaxError::find("module Main exposing (..)\n\n\na = (1\n")
+            .unwrap()
+            .unwrap();
+        let message = FileMessage::new(Path::new("src/Main.elm"), &Err(error.into()), Mode::Check);
+        assert_eq!(message.status, Status::Error);
+        assert_eq!(
+            message.error,
+            Some(ErrorMessage {
+                message: "syntax error: unterminated parenthesized_expr".to_string(),
+                line: Some(4),
+                column: Some(5),
+            })
+        );
+
+        let message = FileMessage::new(Path::new("a.elm"), &Err(anyhow!("boom")), Mode::InPlace);
+        assert_eq!(message.error.unwrap().line, None);
+    }
+
+    fn messages() -> Vec<FileMessage> {
+        vec![
+            FileMessage {
+                path: "src/Main.elm".to_string(),
+                status: Status::WouldReformat,
+                changed_lines: vec![LineRange { start: 3, end: 4 }],
+                error: None,
+                warnings: Vec::new(),
+            },
+            FileMessage {
+                path: "src/Page, 1.elm".to_string(),
+                status: Status::Error,
+                changed_lines: Vec::new(),
+                error: Some(ErrorMessage {
+                    message: "syntax error: unterminated record_expr".to_string(),
+                    line: Some(5),
+                    column: Some(7),
+                }),
+                warnings: Vec::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_github_annotations() {
+        let mut out = Vec::new();
+        for message in messages() {
+            write_github(&message, &mut out).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "::warning file=src/Main.elm,line=3,endLine=4,title=Not formatted::Run elmfmt to format these lines\n\
+             ::error file=src/Page%2C 1.elm,line=5,col=7,title=elmfmt::syntax error: unterminated record_expr\n"
+        );
+    }
+
+    #[test]
+    fn test_sarif_log() {
+        let log = sarif(&messages());
+        assert_eq!(log["version"], "2.1.0");
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["ruleId"], "unformatted");
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["region"],
+            json!({ "startLine": 3, "endLine": 4 })
+        );
+        assert_eq!(results[1]["ruleId"], "syntax-error");
+        assert_eq!(results[1]["level"], "error");
+        assert_eq!(
+            results[1]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "src/Page%2C%201.elm"
+        );
+    }
+
+    #[test]
+    fn test_sarif_artifact_uris() {
+        assert_eq!(artifact_uri("./src/Main.elm"), "src/Main.elm");
+        assert_eq!(artifact_uri("src\\My Page.elm"), "src/My%20Page.elm");
+        assert_eq!(
+            artifact_uri("/home/me/Ä#1.elm"),
+            "file:///home/me/%C3%84%231.elm"
+        );
+        assert_eq!(artifact_uri("C:\\src\\Main.elm"), "file:///C:/src/Main.elm");
+    }
+}
@@ -0,0 +1,47 @@
+use anyhow::{anyhow, Result};
+use tree_sitter::{Node, Parser, Tree};
+
+use crate::LANGUAGE;
+
+/// Parse Elm source code with the bundled grammar
+pub(crate) fn parse(content: &str) -> Result<Tree> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&LANGUAGE.into())
+        .map_err(|e| anyhow!("Failed to load the Elm grammar: {}", e))?;
+    parser
+        .parse(content, None)
+        .ok_or_else(|| anyhow!("Failed to parse Elm code"))
+}
+
+/// Iterate over the direct named children of a node
+pub(crate) fn named_children<'tree>(node: Node<'tree>) -> impl Iterator<Item = Node<'tree>> {
+    (0..node.named_child_count()).filter_map(move |i| node.named_child(i))
+}
+
+/// The source text of a node
+pub(crate) fn text<'a>(content: &'a str, node: Node) -> &'a str {
+    &content[node.byte_range()]
+}
+
+pub(crate) fn is_comment(node: &Node) -> bool {
+    matches!(node.kind(), "line_comment" | "block_comment")
+}
+
+/// Whether a node contains a comment anywhere inside it
+pub(crate) fn has_comment(node: Node) -> bool {
+    named_children(node).any(|child| is_comment(&child) || has_comment(child))
+}
+
+/// The outermost `ERROR` and `MISSING` nodes below a node, in source order
+pub(crate) fn error_nodes(node: Node) -> Vec<Node> {
+    if node.is_error() || node.is_missing() {
+        return vec![node];
+    }
+    if !node.has_error() {
+        return Vec::new();
+    }
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.children(&mut cursor).collect();
+    children.into_iter().flat_map(error_nodes).collect()
+}
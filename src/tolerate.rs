@@ -0,0 +1,261 @@
+//! Formatting of modules with syntax errors (`tolerate_errors`): the top-level
+//! parts with errors are kept as written and the rest is formatted
+
+use anyhow::{bail, Result};
+use std::ops::Range;
+use tree_sitter::Node;
+
+use crate::diagnostics::{diagnose, SyntaxError};
+use crate::syntax::{error_nodes, named_children, parse};
+use crate::Warning;
+
+/// Comment standing in for a region with errors while the rest is formatted,
+/// followed by the index of the region
+const PLACEHOLDER: &str = "-- elmfmt-tolerated-error-";
+
+/// Extend a byte range to the full lines it touches, without the final newline
+fn full_lines(content: &str, range: Range<usize>) -> Range<usize> {
+    let start = content[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let end = content[range.end..]
+        .find('\n')
+        .map_or(content.len(), |i| range.end + i);
+    start..end
+}
+
+/// The lines covered by top-level nodes with syntax errors. Nodes sharing a
+/// line with such a node are part of its region.
+fn error_regions(content: &str, root: Node) -> Vec<Range<usize>> {
+    // Group the top-level nodes into runs that share lines
+    let mut groups: Vec<(Range<usize>, bool)> = Vec::new();
+    for node in named_children(root) {
+        let lines = full_lines(content, node.byte_range());
+        let has_error = node.is_error() || node.has_error();
+        match groups.last_mut() {
+            Some((group, group_has_error)) if lines.start <= group.end => {
+                group.end = group.end.max(lines.end);
+                *group_has_error |= has_error;
+            }
+            _ => groups.push((lines, has_error)),
+        }
+    }
+    groups
+        .into_iter()
+        .filter(|(_, has_error)| *has_error)
+        .flat_map(|(lines, _)| split_declarations(content, lines))
+        .collect()
+}
+
+/// Narrow a region with errors down to its declarations that do not parse on
+/// their own. Error recovery often lets an error swallow the declarations
+/// after it, but top-level declarations start at the beginning of a line.
+fn split_declarations(content: &str, region: Range<usize>) -> Vec<Range<usize>> {
+    let mut starts: Vec<usize> = content[region.clone()]
+        .match_indices('\n')
+        .map(|(i, _)| region.start + i + 1)
+        .filter(|&start| content[start..].starts_with(|c: char| c.is_ascii_alphabetic()))
+        .collect();
+    starts.insert(0, region.start);
+
+    let mut regions: Vec<Range<usize>> = Vec::new();
+    for (i, &start) in starts.iter().enumerate() {
+        // Blank lines after a chunk are left to the formatter
+        let next = starts.get(i + 1).copied().unwrap_or(region.end);
+        let chunk = content[start..next].trim_end();
+        let end = start + chunk.len();
+        let parses = parse(chunk).is_ok_and(|tree| !tree.root_node().has_error());
+        if parses {
+            continue;
+        }
+        match regions.last_mut() {
+            Some(last) if content[last.end..start].trim().is_empty() => last.end = end,
+            _ => regions.push(start..end),
+        }
+    }
+    regions
+}
+
+/// Format a module that may contain syntax errors. The regions with errors are
+/// replaced by placeholder comments, `format` formats the rest, and the
+/// regions are put back as written.
+pub(crate) fn format_around_errors(
+    content: &str,
+    format: impl FnOnce(&str) -> Result<String>,
+) -> Result<String> {
+    let tree = parse(content)?;
+    let root = tree.root_node();
+    if !root.has_error() {
+        return format(content);
+    }
+    if root.is_error() {
+        return Ok(content.to_string());
+    }
+    let masked = Masked::new(content, root)?;
+    let formatted = format(&masked.text)?;
+    masked.restore(content, formatted)
+}
+
+/// Format the declarations touching `range` of a module that may contain
+/// syntax errors elsewhere. `format` gets the module with its regions with
+/// errors masked, and the range within it.
+///
+/// Unless `tolerate` is set, a range touching a region with errors fails with
+/// its syntax error.
+pub(crate) fn format_range_around_errors(
+    content: &str,
+    range: &Range<usize>,
+    tolerate: bool,
+    format: impl FnOnce(&str, &Range<usize>) -> Result<String>,
+) -> Result<String> {
+    let tree = parse(content)?;
+    let root = tree.root_node();
+    if !root.has_error() {
+        return format(content, range);
+    }
+    if root.is_error() {
+        // The whole module is one region with errors
+        if tolerate {
+            return Ok(content.to_string());
+        }
+        return Err(SyntaxError::at(content, root).into());
+    }
+
+    let masked = Masked::new(content, root)?;
+    if !tolerate {
+        if let Some(region) = masked.regions.iter().find(|region| overlaps(region, range)) {
+            let errors = error_nodes(root);
+            let node = errors
+                .iter()
+                .find(|node| overlaps(region, &node.byte_range()))
+                .or(errors.first());
+            if let Some(node) = node {
+                return Err(SyntaxError::at(content, *node).into());
+            }
+        }
+    }
+    let range = masked.offset(range.start)..masked.offset(range.end);
+    let formatted = format(&masked.text, &range)?;
+    masked.restore(content, formatted)
+}
+
+/// Whether a region overlaps a range, or contains it if it is empty
+fn overlaps(region: &Range<usize>, range: &Range<usize>) -> bool {
+    if range.is_empty() {
+        region.start <= range.start && range.start <= region.end
+    } else {
+        region.start < range.end && range.start < region.end
+    }
+}
+
+/// A module with its regions with errors replaced by placeholder comments
+struct Masked {
+    /// The module with placeholders
+    text: String,
+    /// The masked regions of the module
+    regions: Vec<Range<usize>>,
+}
+
+impl Masked {
+    fn new(content: &str, root: Node) -> Result<Self> {
+        if content.contains(PLACEHOLDER) {
+            bail!("Cannot tolerate errors in a module containing `{PLACEHOLDER}`");
+        }
+        let regions = error_regions(content, root);
+        let mut text = String::with_capacity(content.len());
+        let mut copied_until = 0;
+        for (i, region) in regions.iter().enumerate() {
+            text.push_str(&content[copied_until..region.start]);
+            text.push_str(&placeholder(i));
+            copied_until = region.end;
+        }
+        text.push_str(&content[copied_until..]);
+        Ok(Self { text, regions })
+    }
+
+    /// The offset in the masked text of an offset in the module. Offsets
+    /// within a region are moved into its placeholder.
+    fn offset(&self, offset: usize) -> usize {
+        let mut shift = 0isize;
+        for (i, region) in self.regions.iter().enumerate() {
+            let placeholder_len = placeholder(i).len();
+            if offset <= region.start {
+                break;
+            }
+            if offset < region.end {
+                let within = (offset - region.start).min(placeholder_len);
+                return (region.start as isize + shift) as usize + within;
+            }
+            shift += placeholder_len as isize - region.len() as isize;
+        }
+        (offset as isize + shift) as usize
+    }
+
+    /// Put the regions of the module back into the formatted masked text
+    fn restore(&self, content: &str, mut formatted: String) -> Result<String> {
+        for (i, region) in self.regions.iter().enumerate() {
+            let placeholder = placeholder(i);
+            let Some(start) = formatted.find(&placeholder) else {
+                bail!("Formatting lost the code around a syntax error");
+            };
+            formatted.replace_range(start..start + placeholder.len(), &content[region.clone()]);
+        }
+        Ok(formatted)
+    }
+}
+
+/// The placeholder comment of the region with the given index
+fn placeholder(index: usize) -> String {
+    format!("{PLACEHOLDER}{index}")
+}
+
+/// The positions of the syntax errors in a module
+pub(crate) fn syntax_errors(content: &str) -> Result<Vec<Warning>> {
+    let tree = parse(content)?;
+    Ok(error_nodes(tree.root_node())
+        .into_iter()
+        .map(|node| {
+            let (node, message) = diagnose(content, node);
+            let position = node.start_position();
+            let message = format!("{message}, kept as written");
+            Warning {
+                line: position.row + 1,
+                column: position.column + 1,
+                message,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reformat(content: &str) -> Result<String> {
+        Ok(content.replace('=', " = ").replace("  ", " "))
+    }
+
+    #[test]
+    fn test_regions_with_errors_are_kept() {
+        let input = "module Main exposing (..)\n\n\na=1\n\n\nb= (1 +\n\n\nc=2\n";
+        let output = format_around_errors(input, reformat).unwrap();
+        assert_eq!(
+            output,
+            "module Main exposing (..)\n\n\na = 1\n\n\nb= (1 +\n\n\nc = 2\n"
+        );
+        let errors = syntax_errors(input).unwrap();
+        assert!(!errors.is_empty());
+        assert!(errors.iter().all(|error| error.line >= 7));
+    }
+
+    #[test]
+    fn test_offsets_map_into_the_masked_text() {
+        let input = "module Main exposing (..)\n\n\nb= (1 +\n\n\nc=2\n";
+        let tree = parse(input).unwrap();
+        let masked = Masked::new(input, tree.root_node()).unwrap();
+        let c = input.find("c=").unwrap();
+        assert_eq!(&masked.text[masked.offset(c)..], "c=2\n");
+        let b = input.find("b=").unwrap();
+        assert!(masked.text[masked.offset(b)..].starts_with(PLACEHOLDER));
+        let inside = masked.offset(input.find("(1").unwrap());
+        assert!(masked.offset(b) < inside && inside < masked.offset(c));
+    }
+}
@@ -0,0 +1,9 @@
+module Geometry exposing (Point, origin, swap)
+
+type alias Point = (Float, Float)
+
+origin : Point
+origin = (0, 0)
+
+swap : Point -> Point
+swap (x, y) = (y, x)
@@ -0,0 +1,24 @@
+module Main exposing (..)
+
+
+-- elmfmt: off
+lookup : List ( Int, String )
+lookup =
+    [ ( 1,    "one"     )
+    , ( 10,   "ten"     )
+    , ( 100,  "hundred" )
+    ]
+-- elmfmt: on
+
+
+add a   b = a + b
+
+
+-- elmfmt: skip
+matrix =
+    [ [ 1, 0 ]
+    , [ 0, 1 ]
+    ]
+
+
+sub a b = a*b
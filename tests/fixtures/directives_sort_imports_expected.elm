@@ -0,0 +1,15 @@
+module Main exposing (main)
+
+import Dict
+import Set
+
+
+-- elmfmt: off
+import Task exposing   (Task)
+import Array
+-- elmfmt: on
+import Html
+import Maybe
+
+
+x = 1
@@ -0,0 +1,13 @@
+module Main exposing (main)
+
+import Set
+import Dict
+-- elmfmt: off
+import Task exposing   (Task)
+import Array
+-- elmfmt: on
+import Maybe
+import Html
+
+
+x = 1
@@ -0,0 +1,39 @@
+port module Comments exposing (Flags, decodeFlags, save)
+
+import Json.Decode as Decode
+import Json.Encode as Encode
+
+
+
+-- PORTS
+
+
+port save : Encode.Value -> Cmd msg
+
+
+
+-- FLAGS
+
+
+type alias Flags =
+    { user : Maybe String
+    , items : List ( Int, String )
+    }
+
+
+{-| Decode the flags given to `init`
+-}
+decodeFlags : Decode.Value -> Flags
+decodeFlags value =
+    let
+        user =
+            Decode.decodeValue (Decode.field "user" (Decode.nullable Decode.string)) value
+                |> Result.withDefault Nothing
+
+        items =
+            []
+    in
+    -- Items are loaded later
+    { user = user
+    , items = items
+    }
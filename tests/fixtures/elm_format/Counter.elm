@@ -0,0 +1,89 @@
+module Counter exposing
+    ( Model, Msg(..)
+    , init, update, view
+    , main
+    )
+
+{-| A counter application.
+
+
+# Model
+
+@docs Model, Msg
+
+
+# Program
+
+@docs init, update, view
+@docs main
+
+-}
+
+import Browser
+import Html exposing (Html, button, div, text)
+import Html.Attributes exposing (class)
+import Html.Events exposing (onClick)
+
+
+{-| The state of the counter
+-}
+type alias Model =
+    { count : Int
+    , step : Int
+    }
+
+
+{-| Messages
+-}
+type Msg
+    = Increment
+    | Decrement
+    | SetStep Int
+
+
+{-| Initial model
+-}
+init : Model
+init =
+    { count = 0, step = 1 }
+
+
+{-| Update the model
+-}
+update : Msg -> Model -> Model
+update msg model =
+    case msg of
+        Increment ->
+            { model | count = model.count + model.step }
+
+        Decrement ->
+            if model.count - model.step < 0 then
+                { model | count = 0 }
+
+            else
+                { model | count = model.count - model.step }
+
+        SetStep step ->
+            { model | step = step }
+
+
+{-| Render the counter
+-}
+view : Model -> Html Msg
+view model =
+    div [ class "counter" ]
+        [ button [ onClick Decrement ] [ text "-" ]
+        , text (String.fromInt model.count)
+        , button [ onClick Increment ] [ text "+" ]
+        ]
+
+
+{-| The program
+-}
+main : Program () Model Msg
+main =
+    Browser.sandbox
+        { init = init
+        , update = update
+        , view = view
+        }
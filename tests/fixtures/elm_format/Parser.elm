@@ -0,0 +1,60 @@
+module Parser exposing
+    ( Parser
+    , run, digit, map
+    )
+
+{-| Tiny parsers over lists of characters, without imports.
+
+
+# Parsers
+
+@docs Parser
+
+
+# Running
+
+@docs run, digit, map
+
+-}
+
+
+{-| A parser produces a value and the rest of the input.
+-}
+type alias Parser a =
+    List Char -> Maybe ( a, List Char )
+
+
+{-| Run a parser on a string.
+-}
+run : Parser a -> String -> Maybe a
+run parser input =
+    case parser (String.toList input) of
+        Just ( value, [] ) ->
+            Just value
+
+        _ ->
+            Nothing
+
+
+{-| Parse a single digit.
+-}
+digit : Parser Int
+digit chars =
+    case chars of
+        c :: rest ->
+            if Char.isDigit c then
+                Just ( Char.toCode c - 48, rest )
+
+            else
+                Nothing
+
+        [] ->
+            Nothing
+
+
+{-| Transform the value of a parser.
+-}
+map : (a -> b) -> Parser a -> Parser b
+map f parser chars =
+    parser chars
+        |> Maybe.map (\( value, rest ) -> ( f value, rest ))
@@ -0,0 +1,34 @@
+module Pipeline exposing (Point, decode, distance, origin, total)
+
+import Json.Decode as Decode exposing (Decoder)
+
+
+type alias Point =
+    { x : Float
+    , y : Float
+    }
+
+
+origin : Point
+origin =
+    { x = 0, y = 0 }
+
+
+distance : Point -> Point -> Float
+distance a b =
+    sqrt ((a.x - b.x) ^ 2 + (a.y - b.y) ^ 2)
+
+
+total : List Int -> Int
+total numbers =
+    numbers
+        |> List.filter (\n -> n > 0)
+        |> List.map (\n -> n * 2)
+        |> List.sum
+
+
+decode : Decoder Point
+decode =
+    Decode.map2 Point
+        (Decode.field "x" Decode.float)
+        (Decode.field "y" Decode.float)
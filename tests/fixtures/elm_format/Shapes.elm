@@ -0,0 +1,58 @@
+module Shapes exposing (Shape(..), area, describe, largest, perimeter)
+
+import List.Extra
+
+
+type Shape
+    = Circle Float
+    | Rectangle Float Float
+    | Triangle Float Float Float
+
+
+area : Shape -> Float
+area shape =
+    case shape of
+        Circle r ->
+            pi * r * r
+
+        Rectangle w h ->
+            w * h
+
+        Triangle a b c ->
+            let
+                s =
+                    (a + b + c) / 2
+            in
+            sqrt (s * (s - a) * (s - b) * (s - c))
+
+
+perimeter : Shape -> Float
+perimeter shape =
+    case shape of
+        Circle r ->
+            2 * pi * r
+
+        Rectangle w h ->
+            2 * (w + h)
+
+        Triangle a b c ->
+            a + b + c
+
+
+describe : Shape -> String
+describe shape =
+    if area shape > 100 then
+        "large"
+
+    else if area shape > 10 then
+        "medium"
+
+    else
+        "small"
+
+
+largest : List Shape -> Maybe ( Shape, Float )
+largest shapes =
+    shapes
+        |> List.map (\shape -> ( shape, area shape ))
+        |> List.Extra.maximumBy Tuple.second
@@ -0,0 +1,23 @@
+module Version exposing (Version, current, toString)
+
+{-| The version of the application.
+
+@docs Version, current, toString
+
+-}
+
+
+type alias Version =
+    { major : Int
+    , minor : Int
+    }
+
+
+current : Version
+current =
+    { major = 1, minor = 4 }
+
+
+toString : Version -> String
+toString version =
+    String.fromInt version.major ++ "." ++ String.fromInt version.minor
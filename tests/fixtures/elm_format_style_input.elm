@@ -0,0 +1,16 @@
+module Main exposing (main, Model, Msg(..), update)
+import Html exposing (Html, div, text)
+import Browser
+-- MODEL
+type alias Model = { count : Int }
+type Msg = Increment | Decrement
+init : Model
+init = { count = 0 }
+-- UPDATE
+update : Msg -> Model -> Model
+update msg model =
+  case msg of
+    Increment -> { model | count = model.count + 1 }
+    Decrement -> if model.count > 0 then { model | count = model.count - 1 } else model
+main = Browser.sandbox { init = init, update = update, view = \m -> div [] [text (String.fromInt m.count)] }
+pair = (1,2)
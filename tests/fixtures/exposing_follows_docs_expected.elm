@@ -0,0 +1,20 @@
+module Parser exposing
+  ( Parser, run
+  , int, float, (|=)
+  , helper
+  )
+
+{-| Parsing combinators
+
+# Parsers
+@docs Parser, run
+
+# Building blocks
+@docs int, float, (|=)
+
+-}
+
+import Html
+
+
+run = 1
@@ -0,0 +1,16 @@
+module Parser exposing (run, Parser, int, (|=), helper, float)
+
+{-| Parsing combinators
+
+# Parsers
+@docs Parser, run
+
+# Building blocks
+@docs int, float, (|=)
+
+-}
+
+import Html
+
+
+run = 1
@@ -0,0 +1,16 @@
+module Main exposing (Model, Msg(..), main, view)
+
+import Json.Decode as Decode
+  exposing
+    ( Decoder
+    , Value
+    , andThen
+    , field
+    , string
+    , (|>)
+    )
+import Html exposing (Html, div, text)
+
+
+main =
+  text "hello"
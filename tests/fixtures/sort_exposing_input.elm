@@ -0,0 +1,16 @@
+module Main exposing (view, Msg(..), main, Model, Msg)
+
+import Json.Decode as Decode
+    exposing
+        ( string
+        , Decoder
+        , andThen
+        , (|>)
+        , field
+        , Value
+        )
+import Html exposing (text, Html, div, text)
+
+
+main =
+    text "hello"
@@ -0,0 +1,16 @@
+module Main exposing (main)
+
+{-| The main module
+-}
+
+import Browser
+import Dict as D
+import Dict exposing (Dict)
+
+
+-- Views
+import Html exposing (div, Html, text)
+import Html.Events exposing (onClick)
+
+
+main = text "hello"
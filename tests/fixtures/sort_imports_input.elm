@@ -0,0 +1,15 @@
+module Main exposing (main)
+
+{-| The main module
+-}
+
+import Html.Events exposing (onClick)
+-- Views
+import Html exposing (div, Html)
+import Browser
+import Html exposing (text, div)
+import Dict as D
+import Dict exposing (Dict)
+
+
+main = text "hello"
@@ -0,0 +1,14 @@
+module Main exposing (..)
+
+import Html
+
+
+view model=
+  Html.text   model
+
+
+broken = (1 +
+
+
+count : List a -> Int
+count xs=List.length xs
@@ -1,70 +1,30 @@
-use anyhow::{anyhow, Result};
-use elmfmt::LANGUAGE;
+use anyhow::Result;
+use elmfmt::{ConfigError, FormatterConfig, IfStyle, Preset, TupleStyle};
 use std::fs;
 use std::path::PathBuf;
-use topiary_core::{formatter, Language, Operation, TopiaryQuery};
-
-const ELM_QUERY_BASE: &str = include_str!("../queries/elm.scm");
-const IF_HANGING_QUERY: &str = include_str!("../queries/if_hanging.scm");
-const IF_INDENTED_QUERY: &str = include_str!("../queries/if_indented.scm");
-const TUPLE_SPACED_QUERY: &str = include_str!("../queries/tuple_spaced.scm");
-const TUPLE_COMPACT_QUERY: &str = include_str!("../queries/tuple_compact.scm");
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum IfStyle {
-    Indented,
-    Hanging,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum TupleStyle {
-    Compact,
-    Spaced,
-}
-
-fn build_query(if_style: IfStyle, tuple_style: TupleStyle, newlines_between_decls: u8) -> String {
-    let if_query = match if_style {
-        IfStyle::Hanging => IF_HANGING_QUERY,
-        IfStyle::Indented => IF_INDENTED_QUERY,
-    };
-    let tuple_query = match tuple_style {
-        TupleStyle::Spaced => TUPLE_SPACED_QUERY,
-        TupleStyle::Compact => TUPLE_COMPACT_QUERY,
-    };
-    let base_query = format!("{}\n\n{}\n\n{}", ELM_QUERY_BASE, if_query, tuple_query);
-
-    // Replace the placeholder with the configured delimiter for declaration spacing
-    // The config value represents blank lines, so we add 1 for the line-ending newline.
-    let decl_delimiter = "\\n".repeat((newlines_between_decls + 1) as usize);
-    // Section comment delimiter is one less newline since line_comment already has @append_hardline
-    let section_comment_delimiter = "\\n".repeat(newlines_between_decls as usize);
-    base_query
-        .replace("__DECL_DELIMITER__", &decl_delimiter)
-        .replace("__SECTION_COMMENT_DELIMITER__", &section_comment_delimiter)
-}
 
 /// Standard test configuration: 2-space indent, indented if-style, compact tuple-style
 /// These are explicit values chosen for test stability - they don't track the CLI defaults.
 fn format_elm(input: &str) -> Result<String> {
-    format_elm_with_options(input, "  ", IfStyle::Indented, TupleStyle::Compact)
+    format_elm_with_options(input, 2, IfStyle::Indented, TupleStyle::Compact)
 }
 
-fn format_elm_with_indent(input: &str, indent: &str) -> Result<String> {
-    format_elm_with_options(input, indent, IfStyle::Indented, TupleStyle::Compact)
+fn format_elm_with_indent(input: &str, indentation: u8) -> Result<String> {
+    format_elm_with_options(input, indentation, IfStyle::Indented, TupleStyle::Compact)
 }
 
 fn format_elm_with_if_style(input: &str, if_style: IfStyle) -> Result<String> {
-    format_elm_with_options(input, "  ", if_style, TupleStyle::Compact)
+    format_elm_with_options(input, 2, if_style, TupleStyle::Compact)
 }
 
 fn format_elm_with_tuple_style(input: &str, tuple_style: TupleStyle) -> Result<String> {
-    format_elm_with_options(input, "  ", IfStyle::Indented, tuple_style)
+    format_elm_with_options(input, 2, IfStyle::Indented, tuple_style)
 }
 
 fn format_elm_with_newlines(input: &str, newlines_between_decls: u8) -> Result<String> {
     format_elm_full(
         input,
-        "  ",
+        2,
         IfStyle::Indented,
         TupleStyle::Compact,
         newlines_between_decls,
@@ -73,44 +33,28 @@ fn format_elm_with_newlines(input: &str, newlines_between_decls: u8) -> Result<S
 
 fn format_elm_with_options(
     input: &str,
-    indent: &str,
+    indentation: u8,
     if_style: IfStyle,
     tuple_style: TupleStyle,
 ) -> Result<String> {
-    format_elm_full(input, indent, if_style, tuple_style, 2)
+    format_elm_full(input, indentation, if_style, tuple_style, 2)
 }
 
 fn format_elm_full(
     input: &str,
-    indent: &str,
+    indentation: u8,
     if_style: IfStyle,
     tuple_style: TupleStyle,
     newlines_between_decls: u8,
 ) -> Result<String> {
-    let grammar = LANGUAGE;
-    let query_str = build_query(if_style, tuple_style, newlines_between_decls);
-    let query = TopiaryQuery::new(&grammar.into(), &query_str)
-        .map_err(|e| anyhow!("Failed to parse Elm formatting query: {:?}", e))?;
-
-    let language = Language {
-        name: "elm".to_string(),
-        query,
-        grammar: grammar.into(),
-        indent: Some(indent.to_string()),
-    };
-
-    let operation = Operation::Format {
-        skip_idempotence: false,
-        tolerate_parsing_errors: false,
+    let config = FormatterConfig {
+        indentation,
+        if_style,
+        tuple_style,
+        newlines_between_decls,
+        ..FormatterConfig::new()
     };
-
-    let mut input_bytes = input.as_bytes();
-    let mut output = Vec::new();
-
-    formatter(&mut input_bytes, &mut output, &language, operation)
-        .map_err(|e| anyhow!("Failed to format Elm code: {:?}", e))?;
-
-    String::from_utf8(output).map_err(|e| anyhow!("Invalid UTF-8: {}", e))
+    elmfmt::format_elm(input, &config)
 }
 
 fn fixtures_dir() -> PathBuf {
@@ -118,6 +62,10 @@ fn fixtures_dir() -> PathBuf {
 }
 
 fn run_fixture_test(name: &str) {
+    run_fixture_test_with(name, format_elm);
+}
+
+fn run_fixture_test_with(name: &str, format: impl Fn(&str) -> Result<String>) {
     let input_path = fixtures_dir().join(format!("{}_input.elm", name));
     let expected_path = fixtures_dir().join(format!("{}_expected.elm", name));
 
@@ -126,7 +74,7 @@ fn run_fixture_test(name: &str) {
     let expected = fs::read_to_string(&expected_path)
         .unwrap_or_else(|e| panic!("Failed to read {}: {}", expected_path.display(), e));
 
-    let actual = format_elm(&input).unwrap_or_else(|e| panic!("Formatting failed: {}", e));
+    let actual = format(&input).unwrap_or_else(|e| panic!("Formatting failed: {}", e));
 
     assert_eq!(
         actual, expected,
@@ -159,6 +107,77 @@ fn test_multiline_imports_formatting() {
     run_fixture_test("multiline_imports");
 }
 
+#[test]
+fn test_sort_imports_formatting() {
+    let config = FormatterConfig {
+        sort_imports: true,
+        tuple_style: TupleStyle::Compact,
+        ..FormatterConfig::new()
+    };
+    run_fixture_test_with("sort_imports", |input| elmfmt::format_elm(input, &config));
+}
+
+#[test]
+fn test_sort_exposing_formatting() {
+    let config = FormatterConfig {
+        sort_exposing: true,
+        tuple_style: TupleStyle::Compact,
+        ..FormatterConfig::new()
+    };
+    run_fixture_test_with("sort_exposing", |input| elmfmt::format_elm(input, &config));
+}
+
+#[test]
+fn test_exposing_follows_docs_formatting() {
+    let config = FormatterConfig {
+        exposing_follows_docs: true,
+        tuple_style: TupleStyle::Compact,
+        ..FormatterConfig::new()
+    };
+    run_fixture_test_with("exposing_follows_docs", |input| {
+        elmfmt::format_elm(input, &config)
+    });
+}
+
+#[test]
+fn test_elm_format_style_formatting() {
+    let config = FormatterConfig::for_preset(Preset::ElmFormat);
+    run_fixture_test_with("elm_format_style", |input| {
+        elmfmt::format_elm(input, &config)
+    });
+}
+
+#[test]
+fn test_compact_preset_formatting() {
+    let config = FormatterConfig::for_preset(Preset::Compact);
+    run_fixture_test_with("compact_preset", |input| elmfmt::format_elm(input, &config));
+}
+
+/// Files formatted by elm-format must come out of the elm-format preset byte for byte
+#[test]
+fn test_elm_format_corpus_is_unchanged() {
+    let config = FormatterConfig::for_preset(Preset::ElmFormat);
+    let corpus = elmfmt::find_elm_files(&fixtures_dir().join("elm_format"), &Default::default())
+        .unwrap()
+        .files;
+    assert!(!corpus.is_empty(), "The elm-format corpus is empty");
+
+    for path in corpus {
+        let input = fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", path.display(), e));
+        let actual = elmfmt::format_elm(&input, &config)
+            .unwrap_or_else(|e| panic!("Formatting {} failed: {}", path.display(), e));
+        assert_eq!(
+            actual,
+            input,
+            "\n\nelm-format preset changed {}\n\nExpected:\n{}\n\nActual:\n{}\n",
+            path.display(),
+            input,
+            actual
+        );
+    }
+}
+
 #[test]
 fn test_blank_lines_formatting() {
     run_fixture_test("blank_lines");
@@ -738,7 +757,7 @@ type Msg
     = Click
     | Hover
 "#;
-    let result = format_elm_with_indent(input, "  ");
+    let result = format_elm_with_indent(input, 2);
     assert!(result.is_ok(), "Should format with 2-space indent");
     let formatted = result.unwrap();
     // Type variants are indented under the type declaration, with = on same line as first variant
@@ -758,7 +777,7 @@ type Msg
     = Click
     | Hover
 "#;
-    let result = format_elm_with_indent(input, "        ");
+    let result = format_elm_with_indent(input, 8);
     assert!(result.is_ok(), "Should format with 8-space indent");
     let formatted = result.unwrap();
     // Type variants are indented under the type declaration, with = on same line as first variant
@@ -882,6 +901,25 @@ sign n =
     );
 }
 
+#[test]
+fn test_if_style_hanging_multiline_else_if() {
+    let input = r#"module Main exposing (sign)
+
+sign n =
+  if n > 0 then
+    1
+  else if n < 0 then
+    -1
+  else
+    0
+"#;
+    let formatted = format_elm_with_if_style(input, IfStyle::Hanging).unwrap();
+    assert_eq!(
+        formatted, input,
+        "A multi-line else-if chain should be kept"
+    );
+}
+
 // ============================================================================
 // Newlines Between Declarations Tests
 // ============================================================================
@@ -921,19 +959,16 @@ bar = 2
 }
 
 #[test]
-fn test_newlines_between_decls_zero() {
+fn test_newlines_between_decls_zero_is_rejected() {
     let input = r#"module Main exposing (foo, bar)
 foo = 1
 bar = 2
 "#;
-    let result = format_elm_with_newlines(input, 0);
-    assert!(result.is_ok(), "Should format with 0 blank lines");
-    let formatted = result.unwrap();
-    // 0 blank lines means declarations are directly adjacent
+    let error = format_elm_with_newlines(input, 0).unwrap_err();
     assert!(
-        formatted.contains("foo = 1\nbar = 2"),
-        "Should have 0 blank lines between declarations, got:\n{}",
-        formatted
+        error.is::<ConfigError>(),
+        "Should reject 0 blank lines as a config error, got: {}",
+        error
     );
 }
 
@@ -1283,3 +1318,49 @@ fn test_block_comment_in_func_call() {
     // @append_hardline. Also tests binary operators with such function calls.
     run_fixture_test("block_comment_in_func_call");
 }
+
+#[test]
+fn test_directive_comments_keep_regions_verbatim() {
+    run_fixture_test("directives");
+}
+
+#[test]
+fn test_directive_comments_with_sorted_imports() {
+    // Sorting must not move the imports of an off region
+    let config = FormatterConfig {
+        sort_imports: true,
+        ..FormatterConfig::new()
+    };
+    run_fixture_test_with("directives_sort_imports", |input| {
+        elmfmt::format_elm(input, &config)
+    });
+}
+
+#[test]
+fn test_tolerate_errors_formats_around_syntax_errors() {
+    let options = elmfmt::FormatOptions {
+        tolerate_errors: true,
+        ..Default::default()
+    };
+    run_fixture_test_with("tolerate_errors", |input| {
+        elmfmt::format_elm_with_options(input, &FormatterConfig::new(), &options)
+    });
+}
+
+#[test]
+fn test_range_formatting_ignores_syntax_errors_elsewhere() {
+    let formatter = elmfmt::Formatter::new(&FormatterConfig::new()).unwrap();
+    let input = "module Main exposing (..)\n\n\na=1\n\n\nb = (1 +\n\n\nc=2\n";
+
+    let range = elmfmt::TextRange::Lines { start: 10, end: 10 };
+    assert_eq!(
+        formatter.format_range(input, &range).unwrap(),
+        "module Main exposing (..)\n\n\na=1\n\n\nb = (1 +\n\n\nc = 2\n"
+    );
+
+    // A range touching the error still fails
+    let range = elmfmt::TextRange::Lines { start: 7, end: 7 };
+    let error = formatter.format_range(input, &range).unwrap_err();
+    let error = error.downcast_ref::<elmfmt::SyntaxError>().unwrap();
+    assert_eq!((error.line, error.column), (7, 5));
+}